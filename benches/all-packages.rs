@@ -1,4 +1,5 @@
 use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rnix::{TextEdit, TextRange, TextSize};
 
 fn all_packages(c: &mut Criterion) {
     let input = include_str!("all-packages.nix");
@@ -11,5 +12,47 @@ fn all_packages(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, all_packages);
+fn tokenize(c: &mut Criterion) {
+    let input = include_str!("all-packages.nix");
+    let mut group = c.benchmark_group("tokenize");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(30);
+    // `tokenize` collects all tokens into a vector, `tokens` streams them
+    group.bench_with_input("tokenize", input, |b, input| b.iter(|| rnix::tokenize(input).len()));
+    group.bench_with_input("tokens", input, |b, input| b.iter(|| rnix::tokens(input).count()));
+    group.finish();
+}
+
+fn insert(offset: usize, text: &str) -> TextEdit {
+    let offset = TextSize::try_from(offset).unwrap();
+    TextEdit { range: TextRange::empty(offset), replacement: text.to_string() }
+}
+
+fn reparse(c: &mut Criterion) {
+    let input = include_str!("all-packages.nix");
+    let parse = rnix::Root::parse(input);
+    let list = input.find("[ bintools ]").unwrap();
+    let edits = [
+        // Relexes the `bintools` identifier
+        ("token", insert(list + 5, "x")),
+        // Reparses the `[ bintools ]` list
+        ("block", insert(list + 1, " foo")),
+    ];
+
+    let mut group = c.benchmark_group("reparse");
+    group.sample_size(30);
+    for (name, edit) in &edits {
+        group.bench_function(format!("{}/full", name), |b| {
+            b.iter(|| {
+                let mut text = input.to_string();
+                edit.apply(&mut text);
+                rnix::Root::parse(&text)
+            })
+        });
+        group.bench_function(format!("{}/incremental", name), |b| b.iter(|| parse.reparse(edit)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, all_packages, tokenize, reparse);
 criterion_main!(benches);
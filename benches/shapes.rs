@@ -0,0 +1,96 @@
+use std::fmt::Write;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rnix::{ast, tokenizer::Tokenizer};
+use rowan::ast::AstNode;
+
+/// Lists of lambdas and parentheses nested 100 levels deep
+fn deep() -> String {
+    let mut s = String::from("[\n");
+    for i in 0..300 {
+        let depth = 100;
+        s.push_str("  (");
+        for j in 0..depth {
+            write!(s, "x{}: [ (", j).unwrap();
+        }
+        write!(s, "{}", i).unwrap();
+        for _ in 0..depth {
+            s.push_str(") ]");
+        }
+        s.push_str(")\n");
+    }
+    s.push(']');
+    s
+}
+
+/// One attrset with many bindings of all kinds of values
+fn wide() -> String {
+    let mut s = String::from("{\n");
+    for i in 0..20_000 {
+        match i % 4 {
+            0 => writeln!(s, "  attr{} = {};", i, i),
+            1 => writeln!(s, "  attr{}.nested = attr{} + 1;", i, i - 1),
+            2 => writeln!(s, "  inherit (attr{}) name{};", i - 2, i),
+            _ => writeln!(s, "  \"attr {}\" = f attr{} {{ x = {}; }};", i, i - 3, i),
+        }
+        .unwrap();
+    }
+    s.push('}');
+    s
+}
+
+/// Indented strings with interpolations, like in shell scripts of packages
+fn strings() -> String {
+    let mut s = String::from("[\n");
+    for i in 0..2_000 {
+        writeln!(s, "  ''").unwrap();
+        for line in 0..8 {
+            writeln!(s, "    echo \"line {} of ${{name{}}}\" > $out/{}''${{x}}", line, i, line)
+                .unwrap();
+        }
+        writeln!(s, "  ''\n  \"a ${{b}}\\n\\t\"").unwrap();
+    }
+    s.push(']');
+    s
+}
+
+fn shapes(c: &mut Criterion) {
+    for (name, input) in [("deep", deep()), ("wide", wide()), ("strings", strings())] {
+        assert!(rnix::Root::parse(&input).errors().is_empty(), "{} doesn't parse", name);
+
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.sample_size(30);
+        group.bench_with_input("tokenize", &input, |b, input| {
+            b.iter(|| rnix::tokens(input).count())
+        });
+        group.bench_with_input("green", &input, |b, input| {
+            b.iter(|| rnix::parser::parse(Tokenizer::new(input)))
+        });
+        // Parsing and casting every node to an expression, as most users do
+        group.bench_with_input("ast", &input, |b, input| {
+            b.iter(|| {
+                let root = rnix::Root::parse(input).syntax();
+                root.descendants().filter_map(ast::Expr::cast).count()
+            })
+        });
+        group.finish();
+    }
+}
+
+/// Tokenizing the same code with and without a non-ASCII character in it, as
+/// ASCII-only input is tokenized byte by byte instead of char by char
+fn ascii(c: &mut Criterion) {
+    let input = wide();
+    let unicode = format!("# ünïcödé\n{}", input);
+
+    let mut group = c.benchmark_group("ascii");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(30);
+    group.bench_with_input("ascii", &input, |b, input| b.iter(|| rnix::tokens(input).count()));
+    group.bench_with_input("unicode", &unicode, |b, input| b.iter(|| rnix::tokens(input).count()));
+    group.finish();
+}
+
+criterion_group!(benches, shapes, ascii);
+criterion_main!(benches);
@@ -1,4 +1,3 @@
-use rnix::parser::ParseError;
 use std::{env, fs};
 
 fn main() {
@@ -18,14 +17,10 @@ fn main() {
     };
     let ast = rnix::Root::parse(&content);
     for error in ast.errors() {
-        let range = match error {
-            ParseError::Unexpected(range) => range,
-            ParseError::UnexpectedExtra(range) => range,
-            ParseError::UnexpectedWanted(_, range, _) => range,
-            ParseError::UnexpectedDoubleBind(range) => range,
-            ParseError::DuplicatedArgs(range, _) => range,
-            err => {
-                eprintln!("error: {}", err);
+        let range = match error.range() {
+            Some(range) => range,
+            None => {
+                eprintln!("error: {}", error);
                 continue;
             }
         };
@@ -1,20 +1,31 @@
 //! Provides a type system for the AST, in some sense
 
+mod comment_util;
 mod expr_ext;
 mod interpol;
+mod lookup;
+mod merge;
 mod nodes;
 mod operators;
+mod paren_util;
 mod path_util;
 mod str_util;
 mod tokens;
+mod value;
 
 use crate::{NixLanguage, SyntaxKind, SyntaxToken};
 
+pub use comment_util::doc_comment;
 pub use expr_ext::LiteralKind;
 pub use interpol::*;
+pub use lookup::lookup_path;
+pub(crate) use merge::merge_bindings;
+pub use merge::{merged_entries, to_static_map, Merged, MergedTree};
 pub use nodes::*;
 pub use operators::{BinOpKind, UnaryOpKind};
+pub use path_util::{PathKind, PathSegmentPart};
 pub use tokens::*;
+pub use value::{const_eval, NixValue, ValueError};
 
 pub trait AstNode: rowan::ast::AstNode<Language = NixLanguage> {}
 
@@ -49,7 +60,7 @@ mod support {
     }
 
     pub(super) fn token<N: AstNode, T: AstToken>(parent: &N) -> Option<T> {
-        children_tokens(parent).nth(0)
+        children_tokens(parent).next()
     }
 
     /// Token untyped
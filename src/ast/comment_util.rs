@@ -0,0 +1,88 @@
+use crate::{
+    kinds::SyntaxKind::{TOKEN_COMMENT, TOKEN_WHITESPACE},
+    SyntaxNode, SyntaxToken,
+};
+
+/// Get the documentation comment of `node`: either a run of `#` line comments
+/// or a single `/* */` block comment directly preceding it, without the
+/// comment markers. A blank line between the comment and the node breaks the
+/// association, and line comments trailing other code on the same line are
+/// never part of it.
+pub fn doc_comment(node: &SyntaxNode) -> Option<String> {
+    let mut lines = Vec::new();
+    let mut token = node.first_token()?.prev_token();
+
+    while let Some(current) = token {
+        match current.kind() {
+            TOKEN_WHITESPACE if current.text().matches('\n').count() > 1 => break,
+            TOKEN_WHITESPACE => (),
+            TOKEN_COMMENT if current.text().starts_with('#') => {
+                if !starts_line(&current) {
+                    break;
+                }
+                let text = &current.text()[1..];
+                lines.push(text.strip_prefix(' ').unwrap_or(text).to_string());
+            }
+            TOKEN_COMMENT if lines.is_empty() => {
+                let text = current.text();
+                return Some(text[2..text.len() - 2].trim().to_string());
+            }
+            _ => break,
+        }
+        token = current.prev_token();
+    }
+
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+/// Whether only whitespace precedes `token` on its line
+fn starts_line(token: &SyntaxToken) -> bool {
+    match token.prev_token() {
+        None => true,
+        Some(prev) => {
+            prev.kind() == TOKEN_WHITESPACE
+                && (prev.text().contains('\n') || prev.prev_token().is_none())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rowan::ast::AstNode;
+
+    use super::*;
+    use crate::{
+        ast::{self, HasEntry},
+        tests::parse_as,
+    };
+
+    fn docs(s: &str) -> Vec<Option<String>> {
+        let set = parse_as::<ast::AttrSet>(s);
+        set.entries().map(|entry| doc_comment(entry.syntax())).collect()
+    }
+
+    #[test]
+    fn line_comments() {
+        let docs = docs(
+            "{\n  # Some\n  #  docs\n  a = 1;\n  #\n  # more\n  b = 2; # trailing\n  c = 3;\n}",
+        );
+        assert_eq!(docs, [Some("Some\n docs".into()), Some("\nmore".into()), None]);
+    }
+
+    #[test]
+    fn block_comment() {
+        let docs =
+            docs("{\n  /* Some docs */\n  a = 1;\n  # ignored\n  /*\n    more\n  */ b = 2;\n}");
+        assert_eq!(docs, [Some("Some docs".into()), Some("more".into())]);
+    }
+
+    #[test]
+    fn blank_line() {
+        let docs = docs("{\n  # Not docs\n\n  a = 1;\n  /* Not docs either */\n  \n  b = 2;\n}");
+        assert_eq!(docs, [None, None]);
+    }
+}
@@ -1,5 +1,43 @@
+use rowan::ast::AstNode;
+
+use crate::{ast, kinds::SyntaxKind::*, SyntaxNode};
+
+/// A part of a string or path, either literal text or a `${...}`
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum InterpolPart<T> {
     Literal(T),
     Interpolation(super::Interpol),
 }
+
+/// The expressions of all `${...}` within `node` and its descendants, in
+/// strings, paths and dynamic attributes alike, in source order. Escaped ones
+/// like `\${` or `''${` are literal text and aren't included.
+pub fn interpolations(node: &SyntaxNode) -> impl Iterator<Item = ast::Expr> {
+    node.descendants().filter_map(|node| match node.kind() {
+        NODE_INTERPOL => ast::Interpol::cast(node)?.expr(),
+        NODE_DYNAMIC => ast::Dynamic::cast(node)?.expr(),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Root;
+
+    #[test]
+    fn all_hosts() {
+        let root = Root::parse(
+            r#"{ ${a} = "\${not} ${b}"; c = ''''${not} ${d}''; e = ./${f}/g; h = x.${i}; }"#,
+        );
+        let exprs: Vec<_> = interpolations(&root.syntax()).map(|e| e.to_string()).collect();
+        assert_eq!(exprs, ["a", "b", "d", "f", "i"]);
+    }
+
+    #[test]
+    fn nested() {
+        let root = Root::parse(r#""${"${a}" + b}""#);
+        let exprs: Vec<_> = interpolations(&root.syntax()).map(|e| e.to_string()).collect();
+        assert_eq!(exprs, [r#""${a}" + b"#, "a"]);
+    }
+}
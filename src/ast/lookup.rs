@@ -0,0 +1,118 @@
+use crate::ast::{self, Entry, HasEntry};
+
+enum Lookup {
+    Found(ast::Expr),
+    Missing,
+    /// The path might be bound, but that depends on evaluating something
+    Unknown,
+}
+
+/// Get the value bound at `path` within `set`, looking into attribute sets
+/// given literally as values, so that both `{ a = { b = 1; }; }` and
+/// `{ a.b = 1; }` have `1` at `["a", "b"]`.
+///
+/// Returns `None` if the path isn't bound, or if it can't be told without
+/// evaluating code: when a dynamic key like `${x}` or an `inherit` may bind
+/// part of it, or when it leads into a value that isn't an attrset literal.
+/// Those only matter if nothing else binds the path, since binding it twice
+/// is an error. A path bound by several entries that are merged, like `a` in
+/// `{ a.b = 1; a = { c = 2; }; }`, has no single expression as its value, so
+/// it returns `None` as well.
+pub fn lookup_path(set: &ast::AttrSet, path: &[&str]) -> Option<ast::Expr> {
+    match lookup(set, path) {
+        Lookup::Found(value) => Some(value),
+        Lookup::Missing | Lookup::Unknown => None,
+    }
+}
+
+fn lookup(set: &ast::AttrSet, path: &[&str]) -> Lookup {
+    let Some((name, _)) = path.split_first() else { return Lookup::Unknown };
+    let mut unknown = false;
+    // The value bound at exactly `path`, and whether other bindings of
+    // attributes below `path` are merged into it
+    let mut found = None;
+    let mut merged = false;
+    for entry in set.entries() {
+        let attrpath_value = match entry {
+            Entry::Inherit(inherit) => {
+                unknown |= inherit.attrs().any(|attr| attr.static_key().as_deref() == Some(*name));
+                continue;
+            }
+            Entry::AttrpathValue(attrpath_value) => attrpath_value,
+        };
+        let Some(attrpath) = attrpath_value.attrpath() else { continue };
+        let attrs: Vec<_> = attrpath.attrs().collect();
+
+        let mut keys = attrs.iter().zip(path).map(|(attr, name)| (attr.static_key(), name));
+        match keys.find(|(key, name)| key.as_deref() != Some(**name)) {
+            Some((None, _)) => unknown = true,
+            Some((Some(_), _)) => (),
+            None if attrs.is_empty() => (),
+            None if attrs.len() > path.len() => merged = true,
+            None if attrs.len() == path.len() => {
+                merged |= found.is_some();
+                found = attrpath_value.value();
+            }
+            None => match attrpath_value.value() {
+                Some(ast::Expr::AttrSet(nested)) => match lookup(&nested, &path[attrs.len()..]) {
+                    Lookup::Found(value) => return Lookup::Found(value),
+                    Lookup::Missing => (),
+                    Lookup::Unknown => unknown = true,
+                },
+                _ => unknown = true,
+            },
+        }
+    }
+    match found {
+        Some(value) if !merged => Lookup::Found(value),
+        Some(_) => Lookup::Unknown,
+        None if unknown || merged => Lookup::Unknown,
+        None => Lookup::Missing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rowan::ast::AstNode;
+
+    use super::*;
+    use crate::tests::parse_as;
+
+    fn lookup(s: &str, path: &[&str]) -> Option<String> {
+        let set = parse_as::<ast::AttrSet>(s);
+        lookup_path(&set, path).map(|value| value.syntax().to_string())
+    }
+
+    #[test]
+    fn nested() {
+        let s = "{ a = { b = { c = 1; }; d = 2; }; e = 3; }";
+        assert_eq!(lookup(s, &["a", "b", "c"]).as_deref(), Some("1"));
+        assert_eq!(lookup(s, &["a", "b"]).as_deref(), Some("{ c = 1; }"));
+        assert_eq!(lookup(s, &["e"]).as_deref(), Some("3"));
+        assert_eq!(lookup(s, &["a", "x"]), None);
+        assert_eq!(lookup(s, &["e", "x"]), None);
+        assert_eq!(lookup(s, &[]), None);
+    }
+
+    #[test]
+    fn dotted_keys() {
+        let s = r#"{ a.b = 1; a = { c.d = 2; }; "e".f = 3; }"#;
+        assert_eq!(lookup(s, &["a", "b"]).as_deref(), Some("1"));
+        assert_eq!(lookup(s, &["a", "c", "d"]).as_deref(), Some("2"));
+        assert_eq!(lookup(s, &["e", "f"]).as_deref(), Some("3"));
+        assert_eq!(lookup(s, &["a"]), None);
+        assert_eq!(lookup("{ a.b = 1; }", &["a"]), None);
+    }
+
+    #[test]
+    fn unresolvable() {
+        assert_eq!(lookup("{ ${x} = 1; }", &["a"]), None);
+        assert_eq!(lookup("{ a.${x} = 1; }", &["a", "b"]), None);
+        assert_eq!(lookup("{ inherit (x) a; }", &["a", "b"]), None);
+        assert_eq!(lookup("{ a = x; }", &["a", "b"]), None);
+
+        // Anything else binding the path would be a duplicate
+        assert_eq!(lookup("{ ${x} = 1; a = 2; }", &["a"]).as_deref(), Some("2"));
+        assert_eq!(lookup("{ inherit a; b = 1; }", &["b"]).as_deref(), Some("1"));
+    }
+}
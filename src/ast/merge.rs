@@ -0,0 +1,281 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rowan::ast::AstNode;
+
+use crate::{
+    ast::{self, Entry, HasEntry},
+    TextRange,
+};
+
+/// The bindings of an attrset with the ones sharing a prefix merged into
+/// nested sets, as Nix does, see `merged_entries`
+#[derive(Clone, Debug, Default)]
+pub struct MergedTree {
+    /// The names bound at the top level, sorted
+    pub attrs: BTreeMap<String, Merged>,
+    /// The attrpaths bound again in a way that can't be merged, like `a` in
+    /// `{ a = 1; a.b = 2; }`, with the range of the attribute in every
+    /// binding after the first
+    pub conflicts: Vec<(String, TextRange)>,
+}
+
+/// What a name is bound to in a `MergedTree`
+#[derive(Clone, Debug)]
+pub enum Merged {
+    /// A value that isn't merged with anything
+    Value(ast::Expr),
+    /// A name given by `inherit a;` or `inherit (x) a;`
+    Inherited(ast::Inherit),
+    /// A set merged from dotted attrpaths like `a.b = 1; a.c = 2;` and
+    /// attrset literals like `a = { b = 1; };`
+    Set(BTreeMap<String, Merged>),
+}
+
+/// Merge the bindings of `set` that share a prefix, so that `{ a.b = 1; }`
+/// and `{ a = { b = 1; }; }` both have `1` at `a.b`. Like Nix, only attrset
+/// literals that aren't `rec` are merged with other bindings, anything else
+/// bound twice is a conflict. Two literals bound to the same name are only
+/// merged one level deep: `{ a = { b.c = 1; }; a = { b.d = 2; }; }` binds
+/// `a.b` twice, while `{ a = { b.c = 1; }; a.b.d = 2; }` is fine. Bindings
+/// with dynamic keys like `${x}` are skipped, since their names aren't known
+/// statically.
+pub fn merged_entries(set: &ast::AttrSet) -> MergedTree {
+    let mut conflicts = Vec::new();
+    let attrs =
+        merge_bindings(set, &mut |path, _, range| conflicts.push((path.to_string(), range)));
+    MergedTree { attrs, conflicts }
+}
+
+/// Merge the bindings of `set` as `merged_entries` does, calling `conflict`
+/// with the attrpath, the ranges of the attribute in the bindings so far and
+/// its range in the new binding for every conflict, in source order
+pub(crate) fn merge_bindings(
+    set: &ast::AttrSet,
+    conflict: &mut dyn FnMut(&str, &[TextRange], TextRange),
+) -> BTreeMap<String, Merged> {
+    Scope::from_entries(set, "", conflict).into_merged()
+}
+
+/// The values of the bindings of `set` by their attrpath, for reading simple
+/// configuration files. Attrpaths are flattened to their names joined by
+/// dots, so `a.b = 1;` is found at `"a.b"`, while `a = { b = 1; };` is found
+/// at `"a"` with the whole set as its value. Use `merged_entries` for a
+/// nested view instead. Inherits and bindings with dynamic keys like `${x}`
+/// are skipped. If a path is bound more than once, the first binding is kept.
+pub fn to_static_map(set: &ast::AttrSet) -> HashMap<String, ast::Expr> {
+    let mut map = HashMap::new();
+    for attrpath_value in set.attrpath_values() {
+        let Some(attrpath) = attrpath_value.attrpath() else { continue };
+        let Some(value) = attrpath_value.value() else { continue };
+        let path: Option<Vec<_>> = attrpath.attrs().map(|attr| attr.static_key()).collect();
+        if let Some(path) = path.filter(|path| !path.is_empty()) {
+            map.entry(path.join(".")).or_insert(value);
+        }
+    }
+    map
+}
+
+/// The names bound in a set while merging it, each with the ranges of the
+/// attributes binding it
+#[derive(Default)]
+struct Scope(BTreeMap<String, (Vec<TextRange>, Binding)>);
+
+enum Binding {
+    Value(ast::Expr),
+    Inherited(ast::Inherit),
+    Set(Scope),
+}
+
+impl Scope {
+    fn from_entries(
+        set: &impl HasEntry,
+        prefix: &str,
+        conflict: &mut dyn FnMut(&str, &[TextRange], TextRange),
+    ) -> Scope {
+        let mut scope = Scope::default();
+        for entry in set.entries() {
+            match entry {
+                Entry::Inherit(inherit) => {
+                    for attr in inherit.attrs() {
+                        if let Some(name) = attr.static_key() {
+                            let path = [(name, attr.syntax().text_range())];
+                            let inherited = Binding::Inherited(inherit.clone());
+                            scope.insert(&path, inherited, prefix, conflict);
+                        }
+                    }
+                }
+                Entry::AttrpathValue(attrpath_value) => {
+                    let Some(attrpath) = attrpath_value.attrpath() else { continue };
+                    let Some(value) = attrpath_value.value() else { continue };
+                    let path: Option<Vec<_>> = attrpath
+                        .attrs()
+                        .map(|attr| Some((attr.static_key()?, attr.syntax().text_range())))
+                        .collect();
+                    if let Some(path) = path.filter(|path| !path.is_empty()) {
+                        scope.insert(&path, Binding::Value(value), prefix, conflict);
+                    }
+                }
+            }
+        }
+        scope
+    }
+
+    fn insert(
+        &mut self,
+        path: &[(String, TextRange)],
+        value: Binding,
+        prefix: &str,
+        conflict: &mut dyn FnMut(&str, &[TextRange], TextRange),
+    ) {
+        let ((name, range), rest) = path.split_first().unwrap();
+        let full_path = format!("{}{}", prefix, name);
+        let nested_prefix = format!("{}.", full_path);
+
+        // The attrs before the last one of an attrpath go into any set,
+        // creating it if needed
+        if !rest.is_empty() {
+            let (ranges, binding) = self
+                .0
+                .entry(name.clone())
+                .or_insert_with(|| (vec![*range], Binding::Set(Scope::default())));
+            match binding {
+                Binding::Set(nested) => nested.insert(rest, value, &nested_prefix, conflict),
+                _ => {
+                    conflict(&full_path, ranges, *range);
+                    ranges.push(*range);
+                }
+            }
+            return;
+        }
+
+        let literal = match &value {
+            Binding::Value(ast::Expr::AttrSet(set)) if !set.is_rec() => {
+                Some(Scope::from_entries(set, &nested_prefix, conflict))
+            }
+            _ => None,
+        };
+        match (self.0.get_mut(name), literal) {
+            (None, Some(literal)) => {
+                self.0.insert(name.clone(), (vec![*range], Binding::Set(literal)));
+            }
+            (None, None) => {
+                self.0.insert(name.clone(), (vec![*range], value));
+            }
+            // The names of a literal are added to the set, without merging
+            // them any further with the ones already in it
+            (Some((_, Binding::Set(nested))), Some(literal)) => {
+                let mut names: Vec<_> = literal.0.into_iter().collect();
+                names.sort_by_key(|(_, (ranges, _))| ranges[0].start());
+                for (name, (new_ranges, binding)) in names {
+                    match nested.0.get_mut(&name) {
+                        Some((ranges, _)) => {
+                            conflict(&format!("{}{}", nested_prefix, name), ranges, new_ranges[0]);
+                            ranges.push(new_ranges[0]);
+                        }
+                        None => {
+                            nested.0.insert(name, (new_ranges, binding));
+                        }
+                    }
+                }
+            }
+            (Some((ranges, _)), _) => {
+                conflict(&full_path, ranges, *range);
+                ranges.push(*range);
+            }
+        }
+    }
+
+    fn into_merged(self) -> BTreeMap<String, Merged> {
+        self.0
+            .into_iter()
+            .map(|(name, (_, binding))| {
+                let merged = match binding {
+                    Binding::Value(value) => Merged::Value(value),
+                    Binding::Inherited(inherit) => Merged::Inherited(inherit),
+                    Binding::Set(scope) => Merged::Set(scope.into_merged()),
+                };
+                (name, merged)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::parse_as, Root};
+
+    fn merge(s: &str) -> MergedTree {
+        merged_entries(&parse_as::<ast::AttrSet>(s))
+    }
+
+    /// Every value in the tree as `path = value`
+    fn flatten(attrs: &BTreeMap<String, Merged>, prefix: &str, out: &mut Vec<String>) {
+        for (name, merged) in attrs {
+            let path = format!("{}{}", prefix, name);
+            match merged {
+                Merged::Value(value) => out.push(format!("{} = {}", path, value.syntax())),
+                Merged::Inherited(_) => out.push(format!("{} inherited", path)),
+                Merged::Set(nested) => flatten(nested, &format!("{}.", path), out),
+            }
+        }
+    }
+
+    #[test]
+    fn merged() {
+        let tree =
+            merge("{ a.b = 1; a.c = 2; d = { e = 3; }; d.f.g = 4; inherit h; i = rec { }; }");
+        assert!(tree.conflicts.is_empty());
+        let mut values = Vec::new();
+        flatten(&tree.attrs, "", &mut values);
+        assert_eq!(
+            values,
+            ["a.b = 1", "a.c = 2", "d.e = 3", "d.f.g = 4", "h inherited", "i = rec { }"]
+        );
+        assert!(matches!(tree.attrs["a"], Merged::Set(_)));
+    }
+
+    #[test]
+    fn static_map() {
+        let root = Root::parse(
+            r#"{ a = 1; b.c = "x"; b.d.e = 2; "f g" = { h = 3; }; ${i} = 4; j.${k} = 5; inherit l; a = 6; }"#,
+        )
+        .ok()
+        .unwrap();
+        let map = to_static_map(&ast::AttrSet::try_from(root.expr().unwrap()).unwrap());
+        let mut values: Vec<_> =
+            map.iter().map(|(key, value)| format!("{} = {}", key, value.syntax())).collect();
+        values.sort();
+        assert_eq!(values, ["a = 1", "b.c = \"x\"", "b.d.e = 2", "f g = { h = 3; }"]);
+    }
+
+    #[test]
+    fn conflicts() {
+        let s = "{ a = 1; a.b = 2; c.d = 3; c = x; e = rec { }; e.f = 4; inherit g; g.h = 5; }";
+        let tree = merge(s);
+        let conflicts: Vec<_> = tree
+            .conflicts
+            .iter()
+            .map(|(path, range)| (path.as_str(), usize::from(range.start())))
+            .collect();
+        assert_eq!(conflicts, [("a", 9), ("c", 27), ("e", 47), ("g", 67)]);
+        let mut values = Vec::new();
+        flatten(&tree.attrs, "", &mut values);
+        assert_eq!(values, ["a = 1", "c.d = 3", "e = rec { }", "g inherited"]);
+    }
+
+    #[test]
+    fn literals_merge_one_level() {
+        let conflicts = |s| {
+            let tree = merge(s);
+            tree.conflicts
+                .into_iter()
+                .map(|(path, range)| (path, usize::from(range.start())))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(conflicts("{ a.b.c = 1; a = { b.d = 2; }; }"), [("a.b".to_string(), 19)]);
+        assert_eq!(conflicts("{ a = { b.c = 1; }; a = { b.d = 2; }; }"), [("a.b".to_string(), 26)]);
+        assert!(conflicts("{ a = { b.c = 1; }; a.b.d = 2; a = { e = 3; }; }").is_empty());
+        assert!(conflicts("{ a.b.c = 1; a.b = { d = 2; }; }").is_empty());
+    }
+}
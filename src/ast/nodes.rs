@@ -3,7 +3,9 @@ use crate::{NixLanguage, SyntaxKind, SyntaxKind::*, SyntaxNode, SyntaxToken};
 use super::{operators::BinOpKind, support::*, AstNode, UnaryOpKind};
 use rowan::ast::{AstChildren, AstNode as OtherAstNode};
 
+/// The nodes containing bindings: `AttrSet`, `LetIn` and `LegacyLet`
 pub trait HasEntry: AstNode {
+    /// All bindings, both `attrpath = value;` and `inherit`s, in source order
     fn entries(&self) -> AstChildren<Entry>
     where
         Self: Sized,
@@ -11,6 +13,7 @@ pub trait HasEntry: AstNode {
         children(self)
     }
 
+    /// The `attrpath = value;` bindings
     fn attrpath_values(&self) -> AstChildren<AttrpathValue>
     where
         Self: Sized,
@@ -18,12 +21,33 @@ pub trait HasEntry: AstNode {
         children(self)
     }
 
+    /// The `inherit` bindings
     fn inherits(&self) -> AstChildren<Inherit>
     where
         Self: Sized,
     {
         children(self)
     }
+
+    /// The names bound by the entries: the first attribute of every attrpath,
+    /// and every inherited attribute. Names bound by several entries, like `a`
+    /// in `a.b = 1; a.c = 2;`, are yielded once per entry. Attributes whose
+    /// name isn't known statically, like `${foo}`, are skipped.
+    fn bound_names(&self) -> impl Iterator<Item = String>
+    where
+        Self: Sized,
+    {
+        self.entries()
+            .flat_map(|entry| match entry {
+                Entry::Inherit(inherit) => inherit.attrs().collect(),
+                Entry::AttrpathValue(attrpath_value) => attrpath_value
+                    .attrpath()
+                    .and_then(|attrpath| attrpath.attrs().next())
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+            })
+            .filter_map(|attr| attr.static_key())
+    }
 }
 
 macro_rules! node {
@@ -131,11 +155,11 @@ macro_rules! node {
 
 macro_rules! tg {
     (
-        $(#[doc=$doc:tt])?
+        $(#[$meta:meta])*
         $name:ident,
         $token:tt
     ) => {
-        $(#[doc=$doc])?
+        $(#[$meta])*
         pub fn $name(&self) -> Option<SyntaxToken> {
             token_u(self, T![$token])
         }
@@ -192,6 +216,10 @@ node! {
         HasAttr,
     )]
     /// An expression. The fundamental nix ast type.
+    ///
+    /// There is a variant for every kind of node an expression can be, so a
+    /// `match` over it is checked for exhaustiveness by the compiler. Without
+    /// a match, `syntax().kind()` tells the variants apart.
     enum Expr;
 }
 
@@ -204,6 +232,18 @@ node! {
     enum Attr;
 }
 
+impl Attr {
+    /// The name of an identifier key like `foo`, or a string key without
+    /// interpolations like `"foo bar"`. Returns `None` for `${...}` keys.
+    pub fn static_key(&self) -> Option<String> {
+        match self {
+            Attr::Ident(ident) => Some(ident.ident_token()?.text().to_string()),
+            Attr::Str(str) => str.unescape(),
+            Attr::Dynamic(_) => None,
+        }
+    }
+}
+
 node! { #[from(NODE_IDENT)] struct Ident; }
 
 impl Ident {
@@ -216,20 +256,58 @@ node! { #[from(NODE_APPLY)] struct Apply; }
 impl Apply {
     ng! { lambda, Expr, 0 }
     ng! { argument, Expr, 1 }
+
+    /// Split a curried call like `f a b c` into the function being called and
+    /// its arguments in source order, here `(f, [a, b, c])`. A parenthesized
+    /// callee like in `(f a) b` isn't split any further.
+    pub fn flatten(&self) -> (Expr, Vec<Expr>) {
+        let mut args = Vec::new();
+        let mut apply = self.clone();
+        loop {
+            args.extend(apply.argument());
+            // The parser only creates applications after parsing the callee
+            match apply.lambda().expect("application without a callee") {
+                Expr::Apply(inner) => apply = inner,
+                head => {
+                    args.reverse();
+                    return (head, args);
+                }
+            }
+        }
+    }
 }
 
 node! { #[from(NODE_ASSERT)] struct Assert; }
 
 impl Assert {
     tg! { assert_token, assert }
-    ng! { condition, Expr, 0 }
-    ng! { body, Expr, 1 }
+    ng! {
+        /// The `COND` of `assert COND; BODY`
+        condition,
+        Expr,
+        0
+    }
+    tg! { semicolon_token, ; }
+    ng! {
+        /// The `BODY` of `assert COND; BODY`, which is another `Assert` for
+        /// `assert a; assert b; expr`
+        body,
+        Expr,
+        1
+    }
 }
 
 node! { #[from(NODE_ATTRPATH)] struct Attrpath; }
 
 impl Attrpath {
     ng! { attrs, [Attr] }
+
+    /// The attributes of a path like `a.b.c` separated by dots, the same as
+    /// `attrs`. Each has its own range, so e.g. an editor can tell which one
+    /// is under the cursor.
+    pub fn components(&self) -> AstChildren<Attr> {
+        self.attrs()
+    }
 }
 
 node! { #[from(NODE_DYNAMIC)] struct Dynamic; }
@@ -246,11 +324,32 @@ node! { #[from(NODE_IF_ELSE)] struct IfElse; }
 
 impl IfElse {
     tg! { if_token, if }
-    ng! { condition, Expr, 0 }
+    /// The `COND` of `if COND then BODY else ELSE`
+    pub fn condition(&self) -> Option<Expr> {
+        let end = self.then_token().or_else(|| self.else_token());
+        self.expr_between(self.if_token(), end)
+    }
     tg! { then_token, then }
-    ng! { body, Expr, 1 }
+    /// The `BODY` of `if COND then BODY else ELSE`, `None` without the `then`
+    pub fn body(&self) -> Option<Expr> {
+        self.expr_between(Some(self.then_token()?), self.else_token())
+    }
     tg! { else_token, else }
-    ng! { else_body, Expr, 2 }
+    /// The `ELSE` of `if COND then BODY else ELSE`, `None` without the `else`
+    pub fn else_body(&self) -> Option<Expr> {
+        self.expr_between(Some(self.else_token()?), None)
+    }
+
+    /// The first expression after `start` and before `end`, if they exist
+    fn expr_between(&self, start: Option<SyntaxToken>, end: Option<SyntaxToken>) -> Option<Expr> {
+        let start = start.map(|t| t.text_range().end());
+        let end = end.map(|t| t.text_range().start());
+        self.syntax()
+            .children()
+            .filter(|node| start.is_none_or(|start| node.text_range().start() >= start))
+            .take_while(|node| end.is_none_or(|end| node.text_range().end() <= end))
+            .find_map(Expr::cast)
+    }
 }
 
 node! { #[from(NODE_SELECT)] struct Select; }
@@ -259,23 +358,74 @@ impl Select {
     ng! { expr, Expr, 0 }
     tg! { dot_token, . }
     ng! { attrpath, Attrpath, 0 }
-    tg! { or_token, or }
-    ng! { default_expr, Expr, 1 }
+    tg! {
+        /// The `or` keyword of `a.b or default`. An `or` within the attrpath,
+        /// like in `a.or`, is an identifier instead.
+        or_token,
+        or
+    }
+    ng! {
+        /// The default of `a.b or default`
+        default_expr,
+        Expr,
+        1
+    }
+
+    /// The selected path as text for display, like `a.b.c`. The expression
+    /// that is selected from is only included if it's an identifier, so
+    /// `(f x).b` is just `b`. The names of string attributes are included
+    /// without quotes. Returns `None` if an attribute is dynamic, like in
+    /// `a.${b}`.
+    pub fn path_string(&self) -> Option<String> {
+        let mut names = Vec::new();
+        if let Some(Expr::Ident(ident)) = self.expr() {
+            names.push(ident.to_string());
+        }
+        for attr in self.attrpath()?.attrs() {
+            names.push(attr.static_key()?);
+        }
+        Some(names.join("."))
+    }
 }
 
 node! { #[from(NODE_INHERIT)] struct Inherit; }
 
 impl Inherit {
     tg! { inherit_token, inherit }
-    ng! { from, InheritFrom, 0 }
-    ng! { attrs, [Attr] }
+    ng! {
+        /// The `(set)` of `inherit (set) a b;`
+        from,
+        InheritFrom,
+        0
+    }
+    ng! {
+        /// The inherited attributes. Besides identifiers, these may be strings
+        /// like `inherit "a";`, and dynamic attributes in erroneous code.
+        attrs,
+        [Attr]
+    }
+
+    /// The attributes inherited by their plain name, skipping strings
+    pub fn idents(&self) -> impl Iterator<Item = Ident> {
+        self.attrs().filter_map(|attr| match attr {
+            Attr::Ident(ident) => Some(ident),
+            Attr::Dynamic(_) | Attr::Str(_) => None,
+        })
+    }
 }
 
 node! { #[from(NODE_INHERIT_FROM)] struct InheritFrom; }
 
 impl InheritFrom {
     tg! { l_paren_token, '(' }
-    ng! { expr, Expr, 0 }
+    ng! {
+        /// The expression the attributes are taken from, like the select
+        /// `pkgs.lib` of `inherit (pkgs.lib) foo;`. The parentheses are part of
+        /// the `InheritFrom` itself, so this isn't an `Expr::Paren`.
+        expr,
+        Expr,
+        0
+    }
     tg! { r_paren_token, ')' }
 }
 
@@ -292,9 +442,36 @@ impl Interpol {
 node! { #[from(NODE_LAMBDA)] struct Lambda; }
 
 impl Lambda {
-    ng! { param, Param, 0 }
+    ng! {
+        /// The `PARAM` of `PARAM: BODY`, an identifier or a pattern like
+        /// `{ a ? 1, ... }`
+        param,
+        Param,
+        0
+    }
     tg! { token_colon, : }
-    ng! { body, Expr, 0 }
+    ng! {
+        /// The `BODY` of `PARAM: BODY`. Lambdas in default values of a pattern,
+        /// like `a: a` in `{ f ? a: a }: f`, are inside the pattern and never
+        /// mistaken for the body.
+        body,
+        Expr,
+        0
+    }
+
+    /// The number of arguments the function takes when curried: 3 for
+    /// `a: b: c: a`, and 1 for `{ a, b }: a`, which takes a single attrset.
+    /// Only lambdas directly in the body are counted, not those in
+    /// parentheses or behind a `let`.
+    pub fn arity(&self) -> usize {
+        let mut arity = 1;
+        let mut body = self.body();
+        while let Some(Expr::Lambda(lambda)) = body {
+            arity += 1;
+            body = lambda.body();
+        }
+        arity
+    }
 }
 
 node! { #[from(NODE_LEGACY_LET)] struct LegacyLet; }
@@ -305,6 +482,16 @@ impl LegacyLet {
     tg! { let_token, let }
     tg! { curly_open_token, '{' }
     tg! { curly_close_token, '}' }
+
+    /// The value of the `body` attribute, which `let { ... }` evaluates to
+    pub fn body(&self) -> Option<Expr> {
+        self.attrpath_values()
+            .find(|entry| {
+                let attrs: Option<Vec<_>> = entry.attrpath().map(|path| path.attrs().collect());
+                matches!(attrs.as_deref(), Some([attr]) if attr.static_key().as_deref() == Some("body"))
+            })?
+            .value()
+    }
 }
 
 node! { #[from(NODE_LET_IN)] struct LetIn; }
@@ -321,20 +508,52 @@ node! { #[from(NODE_LIST)] struct List; }
 
 impl List {
     tg! { l_brack_token, '[' }
-    ng! { items, [Expr] }
+    ng! {
+        /// The elements of the list. Elements are separated by whitespace, so
+        /// function applications need parentheses and `[ a b (c d) ]` has three.
+        items,
+        [Expr]
+    }
     tg! { r_brack_token, ']' }
+
+    /// The number of elements
+    pub fn len(&self) -> usize {
+        self.items().count()
+    }
+
+    /// Whether the list has no elements, like `[ ]`
+    pub fn is_empty(&self) -> bool {
+        self.items().next().is_none()
+    }
 }
 
 node! { #[from(NODE_BIN_OP)] struct BinOp; }
 
 impl BinOp {
-    ng! { lhs, Expr, 0 }
+    /// The operand before the operator
+    pub fn lhs(&self) -> Option<Expr> {
+        let op = self.operator_token().map(|op| op.text_range().start());
+        self.syntax()
+            .children()
+            .take_while(|node| op.is_none_or(|op| node.text_range().end() <= op))
+            .find_map(Expr::cast)
+    }
+
+    /// The token of the operator. The `-` of `a - -b` is the one between the
+    /// operands, the other one belongs to the negation of `b`.
+    pub fn operator_token(&self) -> Option<SyntaxToken> {
+        children_tokens_u(self).find(|t| BinOpKind::from_kind(t.kind()).is_some())
+    }
 
     pub fn operator(&self) -> Option<BinOpKind> {
-        children_tokens_u(self).find_map(|t| BinOpKind::from_kind(t.kind()))
+        self.operator_token().and_then(|t| BinOpKind::from_kind(t.kind()))
     }
 
-    ng! { rhs, Expr, 1 }
+    /// The operand after the operator, `None` in incomplete code like `a +`
+    pub fn rhs(&self) -> Option<Expr> {
+        let op = self.operator_token()?.text_range().end();
+        self.syntax().children().filter(|node| node.text_range().start() >= op).find_map(Expr::cast)
+    }
 }
 
 node! { #[from(NODE_PAREN)] struct Paren; }
@@ -357,6 +576,16 @@ impl PatEntry {
     ng! { ident, Ident, 0 }
     tg! { question_token, ? }
     ng! { default, Expr, 1 }
+
+    /// The name of the argument
+    pub fn name(&self) -> Option<String> {
+        Some(self.ident()?.ident_token()?.text().to_string())
+    }
+
+    /// Whether the argument has a default, as in `{ a ? 1 }`
+    pub fn has_default(&self) -> bool {
+        self.question_token().is_some()
+    }
 }
 
 node! { #[from(NODE_IDENT_PARAM)] struct IdentParam; }
@@ -380,12 +609,35 @@ impl Pattern {
     ng! { pat_entries, [PatEntry] }
     tg! { ellipsis_token, ... }
     ng! { pat_bind, PatBind, 0 }
+
+    /// The arguments of the pattern, same as `pat_entries`
+    pub fn entries(&self) -> AstChildren<PatEntry> {
+        self.pat_entries()
+    }
+
+    /// Whether the pattern accepts more arguments, as in `{ a, ... }`
+    pub fn ellipsis(&self) -> bool {
+        self.ellipsis_token().is_some()
+    }
+
+    /// The identifier the whole argument is bound to, as in `args@{ a }` and
+    /// `{ a }@args`
+    pub fn at_binding(&self) -> Option<Ident> {
+        self.pat_bind()?.ident()
+    }
 }
 
 node! { #[from(NODE_ROOT)] struct Root; }
 
 impl Root {
-    ng! { expr, Expr, 0 }
+    ng! {
+        /// The expression of the file, or `None` if it has only whitespace and
+        /// comments. Anything after the expression is an error, but it's
+        /// still returned.
+        expr,
+        Expr,
+        0
+    }
 }
 
 node! { #[from(NODE_ATTR_SET)] struct AttrSet; }
@@ -393,9 +645,20 @@ node! { #[from(NODE_ATTR_SET)] struct AttrSet; }
 impl HasEntry for AttrSet {}
 
 impl AttrSet {
-    tg! { rec_token, rec }
+    tg! {
+        /// The `rec` keyword of `rec { ... }`
+        rec_token,
+        rec
+    }
     tg! { l_curly_token, '{' }
     tg! { r_curly_token, '}' }
+
+    /// Whether the bindings of this set can refer to each other, like in
+    /// `rec { a = 1; b = a; }`. Like in Nix, `rec` is always a keyword, so an
+    /// attribute named `rec` has to be quoted: `{ "rec" = 1; }`.
+    pub fn is_rec(&self) -> bool {
+        self.rec_token().is_some()
+    }
 }
 
 node! {
@@ -427,9 +690,20 @@ node! { #[from(NODE_WITH)] struct With; }
 
 impl With {
     tg! { with_token, with }
-    ng! { namespace, Expr, 0 }
+    ng! {
+        /// The `NS` of `with NS; BODY`
+        namespace,
+        Expr,
+        0
+    }
     tg! { semicolon_token, ; }
-    ng! { body, Expr, 1 }
+    ng! {
+        /// The `BODY` of `with NS; BODY`, which is another `With` for
+        /// `with a; with b; expr`
+        body,
+        Expr,
+        1
+    }
 }
 
 node! { #[from(NODE_HAS_ATTR)] struct HasAttr; }
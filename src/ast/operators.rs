@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::SyntaxKind::{self, *};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -24,7 +26,8 @@ pub enum BinOpKind {
 }
 
 impl BinOpKind {
-    /// Get the operation kind from a SyntaxKind in the AST
+    /// Get the operation kind from a SyntaxKind in the AST, the inverse of
+    /// `token`
     pub fn from_kind(token: SyntaxKind) -> Option<Self> {
         match token {
             TOKEN_CONCAT => Some(BinOpKind::Concat),
@@ -51,6 +54,64 @@ impl BinOpKind {
             _ => None,
         }
     }
+
+    /// The kind of the operator's token
+    pub fn token(self) -> SyntaxKind {
+        match self {
+            BinOpKind::Concat => TOKEN_CONCAT,
+            BinOpKind::Update => TOKEN_UPDATE,
+
+            BinOpKind::Add => TOKEN_ADD,
+            BinOpKind::Sub => TOKEN_SUB,
+            BinOpKind::Mul => TOKEN_MUL,
+            BinOpKind::Div => TOKEN_DIV,
+
+            BinOpKind::And => TOKEN_AND_AND,
+            BinOpKind::Equal => TOKEN_EQUAL,
+            BinOpKind::Implication => TOKEN_IMPLICATION,
+            BinOpKind::Less => TOKEN_LESS,
+            BinOpKind::LessOrEq => TOKEN_LESS_OR_EQ,
+            BinOpKind::More => TOKEN_MORE,
+            BinOpKind::MoreOrEq => TOKEN_MORE_OR_EQ,
+            BinOpKind::NotEqual => TOKEN_NOT_EQUAL,
+            BinOpKind::Or => TOKEN_OR_OR,
+
+            BinOpKind::PipeRight => TOKEN_PIPE_RIGHT,
+            BinOpKind::PipeLeft => TOKEN_PIPE_LEFT,
+        }
+    }
+
+    /// The operator as written in the source, like `//` for `Update`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinOpKind::Concat => "++",
+            BinOpKind::Update => "//",
+
+            BinOpKind::Add => "+",
+            BinOpKind::Sub => "-",
+            BinOpKind::Mul => "*",
+            BinOpKind::Div => "/",
+
+            BinOpKind::And => "&&",
+            BinOpKind::Equal => "==",
+            BinOpKind::Implication => "->",
+            BinOpKind::Less => "<",
+            BinOpKind::LessOrEq => "<=",
+            BinOpKind::More => ">",
+            BinOpKind::MoreOrEq => ">=",
+            BinOpKind::NotEqual => "!=",
+            BinOpKind::Or => "||",
+
+            BinOpKind::PipeRight => "|>",
+            BinOpKind::PipeLeft => "<|",
+        }
+    }
+}
+
+impl fmt::Display for BinOpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -60,7 +121,7 @@ pub enum UnaryOpKind {
 }
 
 impl UnaryOpKind {
-    /// Get the operation kind from a token in the AST
+    /// Get the operation kind from a token in the AST, the inverse of `token`
     pub fn from_kind(kind: SyntaxKind) -> Option<Self> {
         match kind {
             TOKEN_INVERT => Some(UnaryOpKind::Invert),
@@ -68,4 +129,26 @@ impl UnaryOpKind {
             _ => None,
         }
     }
+
+    /// The kind of the operator's token
+    pub fn token(self) -> SyntaxKind {
+        match self {
+            UnaryOpKind::Invert => TOKEN_INVERT,
+            UnaryOpKind::Negate => TOKEN_SUB,
+        }
+    }
+
+    /// The operator as written in the source, `!` or `-`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnaryOpKind::Invert => "!",
+            UnaryOpKind::Negate => "-",
+        }
+    }
+}
+
+impl fmt::Display for UnaryOpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
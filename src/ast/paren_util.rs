@@ -0,0 +1,146 @@
+use rowan::ast::AstNode as OtherAstNode;
+
+use crate::{
+    ast::{self, Expr},
+    kinds::SyntaxKind::*,
+    parser::{binding_power, operator_assoc, prefix_binding_power, Assoc},
+    SyntaxNode,
+};
+
+/// How tightly an expression binds to what's around it when it isn't
+/// parenthesized
+enum Power {
+    /// Never split up, like identifiers, lists or `a.b`
+    Atom,
+    /// A prefix operator with the given right binding power
+    Prefix(u8),
+    /// An infix operator with the given binding power, including application
+    Infix((u8, u8)),
+    /// Something extending as far to the right as possible, like a lambda
+    Open,
+}
+
+fn power(expr: &Expr) -> Power {
+    match expr {
+        Expr::Ident(_)
+        | Expr::Literal(_)
+        | Expr::Str(_)
+        | Expr::Path(_)
+        | Expr::List(_)
+        | Expr::AttrSet(_)
+        | Expr::Paren(_) => Power::Atom,
+        Expr::Select(select) if select.or_token().is_none() => Power::Atom,
+        Expr::UnaryOp(op) => match op.operator() {
+            Some(op) => Power::Prefix(prefix_binding_power(op)),
+            None => Power::Open,
+        },
+        Expr::BinOp(op) => match op.operator_token().and_then(|t| binding_power(t.kind())) {
+            Some(power) => Power::Infix(power),
+            None => Power::Open,
+        },
+        Expr::Apply(_) => Power::Infix(binding_power(NODE_APPLY).unwrap()),
+        Expr::HasAttr(_) => Power::Infix(binding_power(T![?]).unwrap()),
+        _ => Power::Open,
+    }
+}
+
+/// Whether `inner` can be an operand of an operator with the binding power
+/// `outer` without parentheses, on its left if `is_lhs`
+fn fits_operand(inner: &Expr, (left, right): (u8, u8), assoc: Assoc, is_lhs: bool) -> bool {
+    match power(inner) {
+        Power::Atom => true,
+        Power::Prefix(power) => power > left.max(right),
+        Power::Infix(power) if power.0.min(power.1) > left.max(right) => true,
+        Power::Infix(power) if power == (left, right) => match assoc {
+            Assoc::Left => is_lhs,
+            Assoc::Right => !is_lhs,
+            Assoc::None => false,
+        },
+        Power::Infix(_) | Power::Open => false,
+    }
+}
+
+impl ast::Paren {
+    /// Whether removing the parentheses doesn't change the meaning of the
+    /// code, like in `((x))`, `(a.b)` or `(a * b) + c`. This errs on the side
+    /// of `false` for anything unusual. Whitespace may be needed in place of
+    /// the removed parentheses, as in `f(x)`.
+    pub fn is_redundant(&self) -> bool {
+        let Some(inner) = self.expr() else { return false };
+        let Some(parent) = self.syntax().parent() else { return true };
+        let is_atom = matches!(power(&inner), Power::Atom);
+        let is_first_child = |parent: &SyntaxNode| {
+            parent.children().find_map(Expr::cast).is_some_and(|it| it.syntax() == self.syntax())
+        };
+
+        match parent.kind() {
+            // Whatever is there extends as far as it can already
+            NODE_ROOT | NODE_PAREN | NODE_ATTRPATH_VALUE | NODE_LET_IN | NODE_WITH
+            | NODE_ASSERT | NODE_LAMBDA | NODE_IF_ELSE | NODE_INTERPOL | NODE_INHERIT_FROM
+            | NODE_DYNAMIC | NODE_PAT_ENTRY => true,
+            NODE_LIST | NODE_SELECT => is_atom,
+            NODE_APPLY if is_first_child(&parent) => {
+                fits_operand(&inner, binding_power(NODE_APPLY).unwrap(), Assoc::Left, true)
+            }
+            NODE_APPLY => is_atom,
+            NODE_HAS_ATTR => fits_operand(&inner, binding_power(T![?]).unwrap(), Assoc::Left, true),
+            NODE_BIN_OP => {
+                let Some(bin_op) = ast::BinOp::cast(parent.clone()) else { return false };
+                let (Some(token), Some(op)) = (bin_op.operator_token(), bin_op.operator()) else {
+                    return false;
+                };
+                let Some(power) = binding_power(token.kind()) else { return false };
+                let is_lhs = bin_op.lhs().is_some_and(|lhs| lhs.syntax() == self.syntax());
+                fits_operand(&inner, power, operator_assoc(op), is_lhs)
+            }
+            NODE_UNARY_OP => {
+                let prefix = ast::UnaryOp::cast(parent).and_then(|op| op.operator());
+                let Some(prefix) = prefix.map(prefix_binding_power) else { return false };
+                match power(&inner) {
+                    Power::Atom => true,
+                    Power::Infix(power) => power.0.min(power.1) > prefix,
+                    Power::Prefix(_) | Power::Open => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ast, Root};
+
+    use super::*;
+
+    /// Whether each pair of parentheses in `s` is redundant, outermost first
+    fn redundant(s: &str) -> Vec<bool> {
+        let root = Root::parse(s).ok().unwrap();
+        root.syntax().descendants().filter_map(ast::Paren::cast).map(|p| p.is_redundant()).collect()
+    }
+
+    #[test]
+    fn redundant_parens() {
+        assert_eq!(redundant("((x))"), [true, true]);
+        assert_eq!(redundant("f (a.b) (g c)"), [true, false]);
+        assert_eq!(redundant("(f a) b"), [true]);
+        assert_eq!(redundant("[ (a.b) (f x) (a.b or c) ]"), [true, false, false]);
+        assert_eq!(redundant("{ a = (x: x); b = (f 1); }"), [true, true]);
+        assert_eq!(redundant("(a * b) + (c / d)"), [true, true]);
+        assert_eq!(redundant("(a - b) - c"), [true]);
+        assert_eq!(redundant("(a ++ b) ++ (c ++ d)"), [false, true]);
+        assert_eq!(redundant("(f x) + (-y) + (!z)"), [true, true, false]);
+        assert_eq!(redundant("!(a + b)"), [true]);
+    }
+
+    #[test]
+    fn necessary_parens() {
+        assert_eq!(redundant("(a + b) * c"), [false]);
+        assert_eq!(redundant("a - (b - c)"), [false]);
+        assert_eq!(redundant("(a == b) == c"), [false]);
+        assert_eq!(redundant("f (-1)"), [false]);
+        assert_eq!(redundant("(x: x) 1"), [false]);
+        assert_eq!(redundant("(if a then b else c).d"), [false]);
+        assert_eq!(redundant("-(a * b)"), [false]);
+    }
+}
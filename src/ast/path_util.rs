@@ -5,11 +5,80 @@ use crate::ast;
 
 use super::{InterpolPart, PathContent};
 
+/// The different flavors of path literals
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PathKind {
+    /// `./foo`, `../foo` or `foo/bar`
+    Relative,
+    /// `/foo`
+    Absolute,
+    /// `~/foo`
+    Home,
+    /// `<foo>`
+    Search,
+}
+
+/// A part of a path segment, see `Path::segments`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PathSegmentPart {
+    Literal(String),
+    Interpolation(ast::Interpol),
+}
+
 impl ast::nodes::Path {
+    pub fn kind(&self) -> PathKind {
+        match self.syntax().first_token().and_then(|t| t.text().chars().next()) {
+            Some('<') => PathKind::Search,
+            Some('~') => PathKind::Home,
+            Some('/') => PathKind::Absolute,
+            _ => PathKind::Relative,
+        }
+    }
+
+    /// Split the path into its `/`-separated segments, leaving out the prefix
+    /// that determines its `kind` (`/`, `~/` or `<...>`). Each segment is a
+    /// list of parts, so a segment that mixes text and interpolations, like
+    /// `foo${bar}`, yields both in one segment.
+    pub fn segments(&self) -> impl Iterator<Item = Vec<PathSegmentPart>> {
+        let kind = self.kind();
+        let mut segments = Vec::new();
+        let mut segment = Vec::new();
+        for (i, part) in self.parts().enumerate() {
+            match part {
+                InterpolPart::Literal(content) => {
+                    let mut text = content.syntax().text();
+                    if i == 0 {
+                        text = match kind {
+                            PathKind::Relative => text,
+                            PathKind::Absolute => &text[1..],
+                            PathKind::Home => &text[2..],
+                            PathKind::Search => text.trim_start_matches('<').trim_end_matches('>'),
+                        };
+                    }
+                    for (j, s) in text.split('/').enumerate() {
+                        if j > 0 && !segment.is_empty() {
+                            segments.push(std::mem::take(&mut segment));
+                        }
+                        if !s.is_empty() {
+                            segment.push(PathSegmentPart::Literal(s.to_string()));
+                        }
+                    }
+                }
+                InterpolPart::Interpolation(interpol) => {
+                    segment.push(PathSegmentPart::Interpolation(interpol))
+                }
+            }
+        }
+        if !segment.is_empty() {
+            segments.push(segment);
+        }
+        segments.into_iter()
+    }
+
     pub fn parts(&self) -> impl Iterator<Item = InterpolPart<PathContent>> {
         self.syntax().children_with_tokens().map(|child| match child {
             NodeOrToken::Token(token) => {
-                assert_eq!(token.kind(), TOKEN_PATH);
+                assert!(matches!(token.kind(), TOKEN_PATH | TOKEN_SPATH));
                 InterpolPart::Literal(PathContent::cast(token).unwrap())
             }
             NodeOrToken::Node(node) => {
@@ -24,10 +93,63 @@ mod tests {
     use rowan::ast::AstNode;
 
     use crate::{
-        ast::{self, AstToken, InterpolPart, PathContent},
+        ast::{self, AstToken, InterpolPart, PathContent, PathKind, PathSegmentPart},
+        tests::parse_as,
         Root,
     };
 
+    fn segments(p: &ast::Path) -> Vec<Vec<String>> {
+        p.segments()
+            .map(|segment| {
+                segment
+                    .into_iter()
+                    .map(|part| match part {
+                        PathSegmentPart::Literal(s) => s,
+                        PathSegmentPart::Interpolation(i) => format!("${{{}}}", i.expr().unwrap()),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn kind() {
+        assert_eq!(parse_as::<ast::Path>("./foo").kind(), PathKind::Relative);
+        assert_eq!(parse_as::<ast::Path>("../foo").kind(), PathKind::Relative);
+        assert_eq!(parse_as::<ast::Path>("foo/bar").kind(), PathKind::Relative);
+        assert_eq!(parse_as::<ast::Path>("/foo").kind(), PathKind::Absolute);
+        assert_eq!(parse_as::<ast::Path>("~/foo").kind(), PathKind::Home);
+        assert_eq!(parse_as::<ast::Path>("<nixpkgs>").kind(), PathKind::Search);
+    }
+
+    #[test]
+    fn segments_interpol() {
+        let p = parse_as::<ast::Path>("./${x}/bar");
+        assert_eq!(segments(&p), [vec!["."], vec!["${x}"], vec!["bar"]]);
+
+        let p = parse_as::<ast::Path>("/a/b${c}d/${e}");
+        assert_eq!(segments(&p), [vec!["a"], vec!["b", "${c}", "d"], vec!["${e}"]]);
+
+        let p = parse_as::<ast::Path>("~/foo/bar");
+        assert_eq!(segments(&p), [["foo"], ["bar"]]);
+
+        let p = parse_as::<ast::Path>("./a${x}/b");
+        assert_eq!(segments(&p), [vec!["."], vec!["a", "${x}"], vec!["b"]]);
+
+        let p = parse_as::<ast::Path>("./a/${x}/b");
+        assert_eq!(segments(&p), [["."], ["a"], ["${x}"], ["b"]]);
+
+        let p = parse_as::<ast::Path>("./a/${x}${y}");
+        assert_eq!(segments(&p), [vec!["."], vec!["a"], vec!["${x}", "${y}"]]);
+    }
+
+    #[test]
+    fn segments_search() {
+        let p = parse_as::<ast::Path>("<nixpkgs/lib>");
+        assert_eq!(p.kind(), PathKind::Search);
+        assert_eq!(segments(&p), [["nixpkgs"], ["lib"]]);
+    }
+
     #[test]
     fn parts() {
         fn assert_eq_ast_ctn(it: &mut dyn Iterator<Item = InterpolPart<PathContent>>, x: &str) {
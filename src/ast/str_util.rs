@@ -6,24 +6,100 @@ use crate::ast;
 use super::{support::children_tokens_u, AstToken, InterpolPart, StrContent};
 
 impl ast::Str {
+    /// The literal text and interpolations of a string in source order, with
+    /// the text as written, including escapes and indentation. See
+    /// `normalized_parts` for their values instead. Error nodes and stray
+    /// tokens of malformed strings are skipped.
     pub fn parts(&self) -> impl Iterator<Item = InterpolPart<StrContent>> {
         self.syntax().children_with_tokens().filter_map(|child| match child {
             NodeOrToken::Token(token) if token.kind() == TOKEN_STRING_CONTENT => {
                 Some(InterpolPart::Literal(StrContent::cast(token).unwrap()))
             }
-            NodeOrToken::Token(token) => {
-                assert!(token.kind() == TOKEN_STRING_START || token.kind() == TOKEN_STRING_END);
-                None
+            NodeOrToken::Token(_) => None,
+            NodeOrToken::Node(node) => ast::Interpol::cast(node).map(InterpolPart::Interpolation),
+        })
+    }
+
+    /// Return the value of a double-quoted string with all escape sequences
+    /// interpreted, or `None` if the string is indented, contains
+    /// interpolations, or is malformed (e.g. ends in a lone backslash).
+    pub fn unescape(&self) -> Option<String> {
+        let mut tokens = self.syntax().children_with_tokens();
+        match tokens.next()?.into_token()? {
+            start if start.kind() == TOKEN_STRING_START && start.text() == "\"" => (),
+            _ => return None,
+        }
+
+        let mut output = String::new();
+        let mut terminated = false;
+        for child in tokens {
+            let token = child.into_token()?;
+            match token.kind() {
+                TOKEN_STRING_CONTENT if !terminated => {
+                    output.push_str(&unescape_double_quoted(token.text())?)
+                }
+                TOKEN_STRING_END if !terminated => terminated = true,
+                _ => return None,
             }
-            NodeOrToken::Node(node) => {
-                assert_eq!(node.kind(), NODE_INTERPOL);
-                Some(InterpolPart::Interpolation(ast::Interpol::cast(node.clone()).unwrap()))
+        }
+        terminated.then_some(output)
+    }
+
+    /// Return the value of an indented string, with the common indentation
+    /// stripped and `''`-escapes interpreted. Returns `None` if the string is
+    /// double-quoted or contains interpolations.
+    ///
+    /// Like Nix, only spaces count as indentation: a line indented with a tab
+    /// prevents any indentation from being stripped. See
+    /// `normalize_indented_with` for expanding tabs instead. Lines may end in
+    /// `\r\n`, which is kept in the value.
+    pub fn normalize_indented(&self) -> Option<String> {
+        self.normalize_indented_impl(None)
+    }
+
+    /// Like `normalize_indented`, but with tabs in the indentation advancing
+    /// to the next multiple of `tab_width` columns, as in editors expanding
+    /// tabs. This doesn't match the value Nix gives the string when it's
+    /// indented with tabs. A tab that only partly falls into the stripped
+    /// indentation is replaced with the spaces for the rest of its width.
+    ///
+    /// Panics if `tab_width` is zero.
+    pub fn normalize_indented_with(&self, tab_width: usize) -> Option<String> {
+        assert!(tab_width > 0, "tab width must not be zero");
+        self.normalize_indented_impl(Some(tab_width))
+    }
+
+    fn normalize_indented_impl(&self, tab_width: Option<usize>) -> Option<String> {
+        if children_tokens_u(self).next().is_none_or(|t| t.text() != "''") {
+            return None;
+        }
+        let mut value = String::new();
+        for part in self.normalized_parts_with(tab_width) {
+            match part {
+                InterpolPart::Literal(literal) => value.push_str(&literal),
+                InterpolPart::Interpolation(_) => return None,
             }
-        })
+        }
+        Some(value)
     }
 
+    /// The parts of a string like `parts`, but with the values of the literal
+    /// text: escapes are interpreted, and the common indentation of indented
+    /// strings is stripped as in `normalize_indented`.
     pub fn normalized_parts(&self) -> Vec<InterpolPart<String>> {
-        let multiline = children_tokens_u(self).next().map_or(false, |t| t.text() == "''");
+        self.normalized_parts_with(None)
+    }
+
+    /// `normalized_parts` with tabs expanded to `tab_width` columns if given,
+    /// see `normalize_indented_with`
+    fn normalized_parts_with(&self, tab_width: Option<usize>) -> Vec<InterpolPart<String>> {
+        let is_indent = |c: char| c == ' ' || (c == '\t' && tab_width.is_some());
+        // The column after indenting `column` columns by `c`
+        let advance = |column: usize, c: char| match tab_width {
+            Some(width) if c == '\t' => (column / width + 1) * width,
+            _ => column + 1,
+        };
+        let multiline = children_tokens_u(self).next().is_some_and(|t| t.text() == "''");
         let mut is_first_literal = true;
         let mut at_start_of_line = true;
         let mut min_indent = 1000000;
@@ -53,16 +129,19 @@ impl ast::Str {
                         if is_first_literal && first_is_literal {
                             is_first_literal = false;
                             if let Some(p) = token_text.find('\n') {
-                                if token_text[0..p].chars().all(|c| c == ' ') {
+                                if line_content(&token_text[0..p]).chars().all(is_indent) {
                                     token_text = &token_text[p + 1..]
                                 }
                             }
                         }
 
-                        for c in token_text.chars() {
+                        let mut chars = token_text.chars().peekable();
+                        while let Some(c) = chars.next() {
                             if at_start_of_line {
-                                if c == ' ' {
-                                    cur_indent += 1;
+                                if is_indent(c) {
+                                    cur_indent = advance(cur_indent, c);
+                                } else if c == '\r' && chars.peek() == Some(&'\n') {
+                                    // Part of a `\r\n` line ending
                                 } else if c == '\n' {
                                     cur_indent = 0;
                                 } else {
@@ -102,7 +181,7 @@ impl ast::Str {
                         if is_first_literal && first_is_literal {
                             is_first_literal = false;
                             if let Some(p) = token_text.find('\n') {
-                                if token_text[0..p].chars().all(|c| c == ' ') {
+                                if line_content(&token_text[0..p]).chars().all(is_indent) {
                                     token_text = &token_text[p + 1..];
                                     if token_text.is_empty() {
                                         i += 1;
@@ -113,13 +192,19 @@ impl ast::Str {
                         }
 
                         let mut str = String::new();
-                        for c in token_text.chars() {
+                        let mut chars = token_text.chars().peekable();
+                        while let Some(c) = chars.next() {
                             if at_start_of_line {
-                                if c == ' ' {
+                                if is_indent(c) {
+                                    let next = advance(cur_dropped, c);
                                     if cur_dropped >= min_indent {
                                         str.push(c);
+                                    } else if next > min_indent {
+                                        str.extend((min_indent..next).map(|_| ' '));
                                     }
-                                    cur_dropped += 1;
+                                    cur_dropped = next;
+                                } else if c == '\r' && chars.peek() == Some(&'\n') {
+                                    str.push(c);
                                 } else if c == '\n' {
                                     cur_dropped = 0;
                                     str.push(c);
@@ -138,7 +223,7 @@ impl ast::Str {
 
                         if i == n - 1 {
                             if let Some(p) = str.rfind('\n') {
-                                if str[p + 1..].chars().all(|c| c == ' ') {
+                                if str[p + 1..].chars().all(is_indent) {
                                     str.truncate(p + 1);
                                 }
                             }
@@ -158,6 +243,11 @@ impl ast::Str {
     }
 }
 
+/// `line` without the `\r` of a `\r\n` line ending
+fn line_content(line: &str) -> &str {
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
 /// Interpret escape sequences in the nix string and return the converted value
 pub fn unescape(input: &str, multiline: bool) -> String {
     let mut output = String::new();
@@ -209,12 +299,76 @@ pub fn unescape(input: &str, multiline: bool) -> String {
     output
 }
 
+/// Like `unescape`, but for the content of a double-quoted string only, and
+/// returning `None` instead of silently dropping a dangling backslash.
+fn unescape_double_quoted(input: &str) -> Option<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut input = input.chars();
+    while let Some(c) = input.next() {
+        match c {
+            '\\' => match input.next()? {
+                'n' => output.push('\n'),
+                'r' => output.push('\r'),
+                't' => output.push('\t'),
+                c => output.push(c),
+            },
+            c => output.push(c),
+        }
+    }
+    Some(output)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Root;
+    use crate::{tests::parse_as, Root};
 
     use super::*;
 
+    #[test]
+    fn str_unescape() {
+        fn unescape(s: &str) -> Option<String> {
+            match Root::parse(s).tree().expr()? {
+                ast::Expr::Str(str) => str.unescape(),
+                _ => unreachable!(),
+            }
+        }
+
+        assert_eq!(unescape(r#""hello""#).as_deref(), Some("hello"));
+        assert_eq!(unescape(r#""""#).as_deref(), Some(""));
+        assert_eq!(unescape(r#""a\nb""#).as_deref(), Some("a\nb"));
+        assert_eq!(unescape(r#""a\tb""#).as_deref(), Some("a\tb"));
+        assert_eq!(unescape(r#""a\rb""#).as_deref(), Some("a\rb"));
+        assert_eq!(unescape(r#""a\\b""#).as_deref(), Some("a\\b"));
+        assert_eq!(unescape(r#""a\"b""#).as_deref(), Some("a\"b"));
+        assert_eq!(unescape(r#""\${x}""#).as_deref(), Some("${x}"));
+        // Nix has no unicode escapes, `\u` is just `u`
+        assert_eq!(unescape(r#""\u00e9""#).as_deref(), Some("u00e9"));
+        assert_eq!(unescape("\"multi\nline\"").as_deref(), Some("multi\nline"));
+
+        assert_eq!(unescape(r#""a${b}c""#), None);
+        assert_eq!(unescape("''indented''"), None);
+        assert_eq!(unescape(r#""trailing\"#), None);
+        assert_eq!(unescape(r#""unterminated"#), None);
+    }
+
+    #[test]
+    fn escaped_interpolation() {
+        fn parts(s: &str) -> Vec<InterpolPart<String>> {
+            parse_as::<ast::Str>(s).normalized_parts()
+        }
+        let is_literal = |parts: &[InterpolPart<String>], s: &str| matches!(parts, [InterpolPart::Literal(literal)] if literal == s);
+
+        assert!(is_literal(&parts("''''${foo}''"), "${foo}"));
+        assert!(is_literal(&parts(r#""\${foo}""#), "${foo}"));
+        assert!(matches!(parts("''${foo}''")[..], [InterpolPart::Interpolation(_)]));
+        assert!(matches!(parts(r#""${foo}""#)[..], [InterpolPart::Interpolation(_)]));
+        // An escaped `''` right before `${` doesn't escape it
+        assert!(matches!(
+            parts("''''' ${foo}''")[..],
+            [InterpolPart::Literal(_), InterpolPart::Interpolation(_)]
+        ));
+    }
+
     #[test]
     fn string_unescapes() {
         assert_eq!(unescape(r#"Hello\n\"World\" :D"#, false), "Hello\n\"World\" :D");
@@ -224,6 +378,60 @@ mod tests {
         assert_eq!(unescape(r#""Hello""#, true), "\"Hello\"");
     }
     #[test]
+    fn normalize_indented() {
+        fn normalize(s: &str) -> Option<String> {
+            parse_as::<ast::Str>(s).normalize_indented()
+        }
+
+        // Examples from the Nix manual
+        assert_eq!(
+            normalize(
+                "''
+  This is the first line.
+  This is the second line.
+    This is the third line.
+''"
+            )
+            .as_deref(),
+            Some("This is the first line.\nThis is the second line.\n  This is the third line.\n")
+        );
+        assert_eq!(normalize("''\n  echo ''${PATH}\n''").as_deref(), Some("echo ${PATH}\n"));
+        assert_eq!(normalize("''\n  two quotes: '''\n''").as_deref(), Some("two quotes: ''\n"));
+        assert_eq!(normalize("''\n  a''\\nb\n''").as_deref(), Some("a\nb\n"));
+
+        // Blank lines don't count towards the indentation
+        assert_eq!(normalize("''\n    a\n\n    b\n  ''").as_deref(), Some("a\n\nb\n"));
+        // Tabs are not indentation
+        assert_eq!(normalize("''\n\ta\n  b\n''").as_deref(), Some("\ta\n  b\n"));
+        assert_eq!(normalize("''''").as_deref(), Some(""));
+        assert_eq!(normalize("''\n   \n  ''").as_deref(), Some("\n"));
+
+        assert_eq!(normalize("''a${b}c''"), None);
+        assert_eq!(normalize(r#""not indented""#), None);
+    }
+    #[test]
+    fn normalize_indented_with() {
+        fn normalize(s: &str, tab_width: usize) -> Option<String> {
+            parse_as::<ast::Str>(s).normalize_indented_with(tab_width)
+        }
+
+        let s = "''\n\ta\n        b\n  \t  c\n\t''";
+        assert_eq!(normalize(s, 8).as_deref(), Some("a\nb\n  c\n"));
+        assert_eq!(normalize(s, 4).as_deref(), Some("a\n    b\n  c\n"));
+        // A tab straddling the stripped indentation leaves the rest of its width
+        assert_eq!(normalize("''\n  a\n \tb\n''", 4).as_deref(), Some("a\n  b\n"));
+        // Without tabs, it's the same as in Nix
+        assert_eq!(normalize("''\n    a\n  b\n''", 4).as_deref(), Some("  a\nb\n"));
+    }
+    #[test]
+    fn normalize_indented_crlf() {
+        let s = "''\r\n    a\r\n \r\n      b\r\n  ''";
+        let root = Root::parse(s).ok().unwrap();
+        assert_eq!(root.syntax().to_string(), s);
+        let str = ast::Str::try_from(root.expr().unwrap()).unwrap();
+        assert_eq!(str.normalize_indented().as_deref(), Some("a\r\n\r\n  b\r\n"));
+    }
+    #[test]
     fn parts_leading_ws() {
         let inp = "''\n  hello\n  world''";
         let expr = Root::parse(inp).ok().unwrap().expr().unwrap();
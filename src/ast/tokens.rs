@@ -8,7 +8,7 @@ use crate::{
 
 macro_rules! token {
     (
-        #[from($kind:ident)]
+        #[from($($kind:ident)|+)]
         $(#[$meta:meta])*
         struct $name:ident;
     ) => {
@@ -24,11 +24,11 @@ macro_rules! token {
 
         impl AstToken for $name {
             fn can_cast(kind: SyntaxKind) -> bool {
-                $kind == kind
+                matches!(kind, $($kind)|+)
             }
 
             fn cast(from: SyntaxToken) -> Option<Self> {
-                if from.kind() == $kind {
+                if Self::can_cast(from.kind()) {
                     Some(Self(from))
                 } else {
                     None
@@ -114,7 +114,7 @@ impl Integer {
     }
 }
 
-token! { #[from(TOKEN_PATH)] struct PathContent; }
+token! { #[from(TOKEN_PATH | TOKEN_SPATH)] struct PathContent; }
 
 token! { #[from(TOKEN_STRING_CONTENT)] struct StrContent; }
 
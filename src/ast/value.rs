@@ -0,0 +1,264 @@
+use std::{cmp::Ordering, fmt, num, path::PathBuf};
+
+use rowan::ast::AstNode as OtherAstNode;
+
+use crate::ast::{self, AstToken, BinOpKind, Expr, LiteralKind, PathKind, UnaryOpKind};
+
+/// The value of a constant expression, see e.g. `Literal::to_value`
+#[derive(Clone, Debug, PartialEq)]
+pub enum NixValue {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Path(PathBuf),
+    Bool(bool),
+    Null,
+}
+
+/// The reasons an expression can't be converted to a `NixValue`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValueError {
+    /// The integer literal doesn't fit into an `i64`
+    Integer(num::ParseIntError),
+    /// The float literal couldn't be parsed
+    Float(num::ParseFloatError),
+    /// The expression isn't a constant, e.g. a string with interpolations
+    NotConstant,
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueError::Integer(err) => write!(f, "invalid integer: {}", err),
+            ValueError::Float(err) => write!(f, "invalid float: {}", err),
+            ValueError::NotConstant => write!(f, "expression is not a constant"),
+        }
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+impl ast::Literal {
+    /// Return the value of the literal. URIs are strings in Nix.
+    pub fn to_value(&self) -> Result<NixValue, ValueError> {
+        match self.kind() {
+            LiteralKind::Integer(it) => {
+                it.value().map(NixValue::Integer).map_err(ValueError::Integer)
+            }
+            LiteralKind::Float(it) => it.value().map(NixValue::Float).map_err(ValueError::Float),
+            LiteralKind::Uri(it) => Ok(NixValue::String(it.syntax().text().to_string())),
+        }
+    }
+}
+
+impl ast::Str {
+    /// Return the value of a string without interpolations, see `unescape`
+    /// and `normalize_indented`
+    pub fn to_value(&self) -> Result<NixValue, ValueError> {
+        self.unescape()
+            .or_else(|| self.normalize_indented())
+            .map(NixValue::String)
+            .ok_or(ValueError::NotConstant)
+    }
+}
+
+impl ast::Path {
+    /// Return a path without interpolations as it's written, without
+    /// resolving it. Search paths like `<nixpkgs>` depend on the environment
+    /// and aren't constants.
+    pub fn to_value(&self) -> Result<NixValue, ValueError> {
+        if self.kind() == PathKind::Search || self.syntax().children().next().is_some() {
+            return Err(ValueError::NotConstant);
+        }
+        Ok(NixValue::Path(PathBuf::from(self.syntax().text().to_string())))
+    }
+}
+
+impl ast::Ident {
+    /// Return the value of `true`, `false` and `null`. These are ordinary
+    /// identifiers that might be shadowed, which isn't taken into account.
+    pub fn to_value(&self) -> Result<NixValue, ValueError> {
+        match self.ident_token().as_ref().map(|token| token.text()) {
+            Some("true") => Ok(NixValue::Bool(true)),
+            Some("false") => Ok(NixValue::Bool(false)),
+            Some("null") => Ok(NixValue::Null),
+            _ => Err(ValueError::NotConstant),
+        }
+    }
+}
+
+/// Evaluate an expression made up of only literals and operators on them,
+/// like `1 + 2 * 3`, `"a" + "b"` or `!(1 < 2.5)`. This covers arithmetic,
+/// string concatenation with `+`, `&&`, `||`, `->` and `!` on booleans and
+/// comparisons. Integers are converted to floats when mixed with them, like
+/// Nix does. Anything else is `None`, including any identifier other than
+/// `true`, `false` and `null`, and operations Nix would fail on, like an
+/// integer overflow or a division by zero.
+pub fn const_eval(expr: &Expr) -> Option<NixValue> {
+    match expr {
+        Expr::Literal(it) => it.to_value().ok(),
+        Expr::Str(it) => it.to_value().ok(),
+        Expr::Path(it) => it.to_value().ok(),
+        Expr::Ident(it) => it.to_value().ok(),
+        Expr::Paren(it) => const_eval(&it.expr()?),
+        Expr::UnaryOp(it) => match (it.operator()?, const_eval(&it.expr()?)?) {
+            (UnaryOpKind::Invert, NixValue::Bool(b)) => Some(NixValue::Bool(!b)),
+            (UnaryOpKind::Negate, NixValue::Integer(i)) => i.checked_neg().map(NixValue::Integer),
+            (UnaryOpKind::Negate, NixValue::Float(f)) => Some(NixValue::Float(-f)),
+            _ => None,
+        },
+        Expr::BinOp(it) => {
+            let lhs = const_eval(&it.lhs()?)?;
+            let rhs = const_eval(&it.rhs()?)?;
+            eval_bin_op(it.operator()?, lhs, rhs)
+        }
+        _ => None,
+    }
+}
+
+fn eval_bin_op(op: BinOpKind, lhs: NixValue, rhs: NixValue) -> Option<NixValue> {
+    use NixValue::*;
+
+    let value = match (op, lhs, rhs) {
+        (BinOpKind::Add, String(a), String(b)) => String(a + &b),
+        (BinOpKind::And, Bool(a), Bool(b)) => Bool(a && b),
+        (BinOpKind::Or, Bool(a), Bool(b)) => Bool(a || b),
+        (BinOpKind::Implication, Bool(a), Bool(b)) => Bool(!a || b),
+        (BinOpKind::Equal, a, b) => Bool(values_eq(&a, &b)),
+        (BinOpKind::NotEqual, a, b) => Bool(!values_eq(&a, &b)),
+        (BinOpKind::Less, a, b) => Bool(compare(&a, &b)?.is_lt()),
+        (BinOpKind::LessOrEq, a, b) => Bool(compare(&a, &b)?.is_le()),
+        (BinOpKind::More, a, b) => Bool(compare(&a, &b)?.is_gt()),
+        (BinOpKind::MoreOrEq, a, b) => Bool(compare(&a, &b)?.is_ge()),
+        (op, Integer(a), Integer(b)) => Integer(match op {
+            BinOpKind::Add => a.checked_add(b)?,
+            BinOpKind::Sub => a.checked_sub(b)?,
+            BinOpKind::Mul => a.checked_mul(b)?,
+            BinOpKind::Div => a.checked_div(b)?,
+            _ => return None,
+        }),
+        (op, a, b) => {
+            let (a, b) = (as_float(&a)?, as_float(&b)?);
+            Float(match op {
+                BinOpKind::Add => a + b,
+                BinOpKind::Sub => a - b,
+                BinOpKind::Mul => a * b,
+                BinOpKind::Div if b != 0.0 => a / b,
+                _ => return None,
+            })
+        }
+    };
+    Some(value)
+}
+
+fn as_float(value: &NixValue) -> Option<f64> {
+    match *value {
+        NixValue::Integer(i) => Some(i as f64),
+        NixValue::Float(f) => Some(f),
+        _ => None,
+    }
+}
+
+/// `==` in Nix, where numbers are equal regardless of their type
+fn values_eq(a: &NixValue, b: &NixValue) -> bool {
+    match (as_float(a), as_float(b)) {
+        (Some(_), Some(_)) if a != b => compare(a, b) == Some(Ordering::Equal),
+        _ => a == b,
+    }
+}
+
+/// `<` and friends in Nix, which fail for values of different types except
+/// for numbers
+fn compare(a: &NixValue, b: &NixValue) -> Option<Ordering> {
+    match (a, b) {
+        (NixValue::Integer(a), NixValue::Integer(b)) => Some(a.cmp(b)),
+        (NixValue::String(a), NixValue::String(b)) => Some(a.cmp(b)),
+        (NixValue::Path(a), NixValue::Path(b)) => Some(a.cmp(b)),
+        _ => as_float(a)?.partial_cmp(&as_float(b)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{ast::Expr, tests::parse_as};
+
+    fn value(s: &str) -> Result<NixValue, ValueError> {
+        match parse_as::<Expr>(s) {
+            Expr::Literal(it) => it.to_value(),
+            Expr::Str(it) => it.to_value(),
+            Expr::Path(it) => it.to_value(),
+            Expr::Ident(it) => it.to_value(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn integer() {
+        assert_eq!(value("42"), Ok(NixValue::Integer(42)));
+        assert_eq!(value("9223372036854775807"), Ok(NixValue::Integer(i64::MAX)));
+        assert!(matches!(value("9223372036854775808"), Err(ValueError::Integer(_))));
+    }
+
+    #[test]
+    fn float() {
+        assert_eq!(value("1.5"), Ok(NixValue::Float(1.5)));
+        assert_eq!(value("1.2e3"), Ok(NixValue::Float(1200.0)));
+        assert_eq!(value("1.E-2"), Ok(NixValue::Float(0.01)));
+        assert_eq!(value("2."), Ok(NixValue::Float(2.0)));
+    }
+
+    #[test]
+    fn path() {
+        assert_eq!(value("./foo/bar.nix"), Ok(NixValue::Path(PathBuf::from("./foo/bar.nix"))));
+        assert_eq!(value("/etc"), Ok(NixValue::Path(PathBuf::from("/etc"))));
+        assert_eq!(value("./foo/${bar}"), Err(ValueError::NotConstant));
+        assert_eq!(value("<nixpkgs>"), Err(ValueError::NotConstant));
+    }
+
+    #[test]
+    fn others() {
+        assert_eq!(value(r#""a\nb""#), Ok(NixValue::String("a\nb".into())));
+        assert_eq!(value("''\n  a\n''"), Ok(NixValue::String("a\n".into())));
+        assert_eq!(value(r#""a${b}""#), Err(ValueError::NotConstant));
+        assert_eq!(value("https://nixos.org"), Ok(NixValue::String("https://nixos.org".into())));
+        assert_eq!(value("true"), Ok(NixValue::Bool(true)));
+        assert_eq!(value("null"), Ok(NixValue::Null));
+        assert_eq!(value("x"), Err(ValueError::NotConstant));
+    }
+
+    fn eval(s: &str) -> Option<NixValue> {
+        const_eval(&parse_as(s))
+    }
+
+    #[test]
+    fn constant() {
+        assert_eq!(eval("1 + 2 * 3"), Some(NixValue::Integer(7)));
+        assert_eq!(eval("(1 + 2) * 3 - -1"), Some(NixValue::Integer(10)));
+        assert_eq!(eval("7 / 2"), Some(NixValue::Integer(3)));
+        assert_eq!(eval("1 + 0.5"), Some(NixValue::Float(1.5)));
+        assert_eq!(eval("3 / 2.0"), Some(NixValue::Float(1.5)));
+        assert_eq!(eval(r#""a" + "b""#), Some(NixValue::String("ab".into())));
+        assert_eq!(eval("true && false"), Some(NixValue::Bool(false)));
+        assert_eq!(eval("!false || false"), Some(NixValue::Bool(true)));
+        assert_eq!(eval("false -> null == null"), Some(NixValue::Bool(true)));
+        assert_eq!(eval("1 == 1.0"), Some(NixValue::Bool(true)));
+        assert_eq!(eval(r#"1 != "1""#), Some(NixValue::Bool(true)));
+        assert_eq!(eval("1 < 1.5"), Some(NixValue::Bool(true)));
+        assert_eq!(eval(r#""b" >= "a""#), Some(NixValue::Bool(true)));
+    }
+
+    #[test]
+    fn not_const() {
+        assert_eq!(eval("1 + x"), None);
+        assert_eq!(eval("true && x"), None);
+        assert_eq!(eval(r#""a" + 1"#), None);
+        assert_eq!(eval("1 < true"), None);
+        assert_eq!(eval("1 / 0"), None);
+        assert_eq!(eval("9223372036854775807 + 1"), None);
+        assert_eq!(eval("[ 1 ] ++ [ 2 ]"), None);
+        assert_eq!(eval("f 1"), None);
+    }
+}
@@ -0,0 +1,95 @@
+//! Construction of syntax trees without going through source text
+//!
+//! Every function returns a new root node with the same structure, including
+//! whitespace, the parser would produce for the code it prints as. Nodes can
+//! be passed on to other functions of this module to build larger trees.
+
+use rowan::{GreenNode, GreenToken, Language, NodeOrToken};
+
+use crate::{
+    NixLanguage,
+    SyntaxKind::{self, *},
+    SyntaxNode,
+};
+
+type GreenElement = NodeOrToken<GreenNode, GreenToken>;
+
+fn token(kind: SyntaxKind, text: &str) -> GreenElement {
+    NodeOrToken::Token(GreenToken::new(NixLanguage::kind_to_raw(kind), text))
+}
+
+fn child(node: &SyntaxNode) -> GreenElement {
+    NodeOrToken::Node(node.green().into_owned())
+}
+
+fn node(kind: SyntaxKind, children: Vec<GreenElement>) -> SyntaxNode {
+    SyntaxNode::new_root(GreenNode::new(NixLanguage::kind_to_raw(kind), children))
+}
+
+/// An identifier. `name` isn't checked, so it should be a valid identifier
+/// and not a keyword.
+pub fn ident(name: &str) -> SyntaxNode {
+    node(NODE_IDENT, vec![token(TOKEN_IDENT, name)])
+}
+
+/// A non-negative integer literal. Negative numbers are unary operations in Nix.
+pub fn integer(value: u64) -> SyntaxNode {
+    node(NODE_LITERAL, vec![token(TOKEN_INTEGER, &value.to_string())])
+}
+
+/// A binding `key = value;` of a single attribute `key`, e.g. an `ident`
+pub fn key_value(key: SyntaxNode, value: SyntaxNode) -> SyntaxNode {
+    let attrpath = node(NODE_ATTRPATH, vec![child(&key)]);
+    node(
+        NODE_ATTRPATH_VALUE,
+        vec![
+            child(&attrpath),
+            token(TOKEN_WHITESPACE, " "),
+            token(TOKEN_ASSIGN, "="),
+            token(TOKEN_WHITESPACE, " "),
+            child(&value),
+            token(TOKEN_SEMICOLON, ";"),
+        ],
+    )
+}
+
+/// An attribute set `{ entry₁ entry₂ … }` of entries like `key_value`, all on
+/// a single line
+pub fn attr_set(entries: impl IntoIterator<Item = SyntaxNode>) -> SyntaxNode {
+    let mut children = vec![token(TOKEN_L_BRACE, "{"), token(TOKEN_WHITESPACE, " ")];
+    for entry in entries {
+        children.push(child(&entry));
+        children.push(token(TOKEN_WHITESPACE, " "));
+    }
+    children.push(token(TOKEN_R_BRACE, "}"));
+    node(NODE_ATTR_SET, children)
+}
+
+#[cfg(test)]
+mod tests {
+    use rowan::ast::AstNode;
+
+    use super::*;
+    use crate::Root;
+
+    fn assert_parses_back(node: &SyntaxNode) {
+        let root = Root::parse(&node.to_string()).ok().unwrap();
+        assert_eq!(*root.expr().unwrap().syntax().green(), *node.green());
+    }
+
+    #[test]
+    fn attr_set() {
+        let set = super::attr_set([key_value(ident("x"), integer(1))]);
+        assert_eq!(set.to_string(), "{ x = 1; }");
+        assert_parses_back(&set);
+    }
+
+    #[test]
+    fn nested() {
+        let inner = super::attr_set([]);
+        let set =
+            super::attr_set([key_value(ident("a"), inner), key_value(ident("b"), ident("a"))]);
+        assert_eq!(set.to_string(), "{ a = { }; b = a; }");
+        assert_parses_back(&set);
+    }
+}
@@ -0,0 +1,121 @@
+//! Rendering of parse errors with the source code they refer to
+
+use std::fmt::Write;
+
+use crate::{parser::ParseError, LineIndex, TextRange, TextSize};
+
+/// Render `errors` in `source` for humans, in the style of rustc: every error
+/// is followed by its line and column, its line of source code with the one
+/// before and after it, and a `^^^` underline of its range. Errors without a
+/// range point at the end of `source`. A range spanning several lines is
+/// underlined until the end of its first line, followed by `...`.
+///
+/// ```text
+/// error: unexpected TOKEN_SEMICOLON at 8..9, wanted any of [...]
+///  --> 2:7
+///   |
+/// 1 | {
+/// 2 |   a = ;
+///   |       ^
+/// 3 | }
+/// ```
+pub fn render_errors(source: &str, errors: &[ParseError]) -> String {
+    let index = LineIndex::new(source);
+    let lines: Vec<&str> =
+        source.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)).collect();
+    let eof = TextRange::empty(TextSize::of(source));
+
+    let mut out = String::new();
+    for error in errors {
+        let range = error.range().unwrap_or(eof);
+        let (line, col) = index.line_col(range.start());
+        // The line of the last character, which is the one of the start for
+        // a range ending right after a newline
+        let last_char = range.end().checked_sub(1.into()).filter(|_| !range.is_empty());
+        let (end_line, _) = index.line_col(last_char.unwrap_or(range.end()));
+        let (line, col, end_line) = (line as usize, col as usize, end_line as usize);
+        let text = lines[line];
+        let end_col = match index.line_col(range.end()) {
+            (end, end_col) if end as usize == line => (end_col as usize).max(col + 1),
+            _ => text.len(),
+        };
+
+        let first = line.saturating_sub(1);
+        let last = (line + 1).min(lines.len() - 1);
+        let width = (last + 1).to_string().len();
+        let gutter = " ".repeat(width);
+
+        writeln!(out, "error: {}", error).unwrap();
+        writeln!(out, "{}--> {}:{}", gutter, line + 1, text[..col].chars().count() + 1).unwrap();
+        writeln!(out, "{} |", gutter).unwrap();
+        for (n, source_line) in lines.iter().enumerate().take(line + 1).skip(first) {
+            writeln!(out, "{:>width$} | {}", n + 1, source_line, width = width).unwrap();
+        }
+
+        // Tabs are kept so the underline lines up however they're displayed
+        let padding: String =
+            text[..col].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let underline_len = text.get(col..end_col).map_or(1, |s| s.chars().count()).max(1);
+        write!(out, "{} | {}{}", gutter, padding, "^".repeat(underline_len)).unwrap();
+        if end_line > line {
+            out.push_str(" ...");
+        }
+        out.push('\n');
+        if last > line {
+            writeln!(out, "{:>width$} | {}", last + 1, lines[last], width = width).unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::render_errors;
+    use crate::Root;
+
+    fn render(source: &str) -> String {
+        render_errors(source, Root::parse(source).errors())
+    }
+
+    #[test]
+    fn single_error() {
+        expect![[r#"
+            error: unexpected TOKEN_SEMICOLON at 8..9, wanted any of [TOKEN_L_PAREN, TOKEN_REC, TOKEN_L_BRACE, TOKEN_L_BRACK, TOKEN_STRING_START, TOKEN_IDENT]
+             --> 2:7
+              |
+            1 | {
+            2 |   a = ;
+              |       ^
+            3 | }
+        "#]]
+        .assert_eq(&render("{\n  a = ;\n}"));
+    }
+
+    #[test]
+    fn multiline() {
+        expect![[r#"
+            error: unexpected token at 2..9
+             --> 1:3
+              |
+            1 | 1 ]
+              |   ^ ...
+            2 |   2
+        "#]]
+        .assert_eq(&render("1 ]\n  2\n3"));
+    }
+
+    #[test]
+    fn range_ending_at_newline() {
+        expect![[r#"
+            error: unterminated string at 1..2
+             --> 1:2
+              |
+            1 | "
+              |  ^
+            2 | 
+        "#]]
+        .assert_eq(&render("\"\n\n\n"));
+    }
+}
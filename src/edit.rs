@@ -0,0 +1,231 @@
+//! Modification of parsed syntax trees that keeps their formatting
+//!
+//! Every function returns the root of a new tree with the change applied,
+//! leaving the tree it's given untouched.
+
+use rowan::{ast::AstNode, GreenNode, GreenToken, Language, NodeOrToken};
+
+use crate::{ast, NixLanguage, SyntaxElement, SyntaxKind::*, SyntaxNode, TextSize};
+
+type GreenElement = NodeOrToken<GreenNode, GreenToken>;
+
+fn whitespace(text: &str) -> GreenElement {
+    NodeOrToken::Token(GreenToken::new(NixLanguage::kind_to_raw(TOKEN_WHITESPACE), text))
+}
+
+fn green(element: &SyntaxElement) -> GreenElement {
+    match element {
+        NodeOrToken::Node(node) => NodeOrToken::Node(node.green().into_owned()),
+        NodeOrToken::Token(token) => NodeOrToken::Token(token.green().to_owned()),
+    }
+}
+
+/// The whitespace at the start of the line `offset` is on
+fn line_indent(root: &SyntaxNode, offset: TextSize) -> String {
+    let text = root.to_string();
+    let line = &text[..usize::from(offset)];
+    let line = &line[line.rfind('\n').map_or(0, |i| i + 1)..];
+    line.chars().take_while(|&c| c == ' ' || c == '\t').collect()
+}
+
+/// Add the binding `key = value_src;` to `set`. `key` is the source of an
+/// attrpath like `a.b` or `"c d"`, and `value_src` that of an expression,
+/// which is inserted as is.
+///
+/// With `sorted`, the binding goes before the first one with a greater
+/// attrpath, which keeps bindings sorted alphabetically if they are already.
+/// Otherwise it becomes the last entry. It's put on its own line, indented
+/// like the other entries, if they are on lines of their own, and at the
+/// indentation of the set plus two spaces for a set without any entries
+/// spanning multiple lines. Comments after an entry on the same line stay with
+/// it.
+///
+/// Returns `None` if `key = value_src;` isn't exactly one binding without
+/// errors, like for the key `a b`. Otherwise the new tree parses back to the
+/// same tree, as long as `set` had no errors either.
+pub fn insert_entry(
+    set: &ast::AttrSet,
+    key: &str,
+    value_src: &str,
+    sorted: bool,
+) -> Option<SyntaxNode> {
+    let binding = format!("{} = {};", key, value_src);
+    let parse = ast::Root::parse(&format!("{{ {} }}", binding));
+    if parse.has_errors() {
+        return None;
+    }
+    let mut entries =
+        parse.syntax().descendants().filter(|node| node.kind() == NODE_ATTRPATH_VALUE);
+    let new_entry = match (entries.next(), entries.next()) {
+        (Some(entry), None) if entry.to_string() == binding => entry,
+        _ => return None,
+    };
+    let new_entry = NodeOrToken::Node(new_entry.green().into_owned());
+
+    let set = set.syntax();
+    let children: Vec<SyntaxElement> = set.children_with_tokens().collect();
+    let entries: Vec<usize> = (0..children.len())
+        .filter(|&i| matches!(children[i].kind(), NODE_ATTRPATH_VALUE | NODE_INHERIT))
+        .collect();
+    // The whitespace starting the line of an entry, or separating it from the
+    // previous one if on the same line
+    let separator = |i: usize| match children[..i].last() {
+        Some(NodeOrToken::Token(token)) if token.kind() == TOKEN_WHITESPACE => {
+            let text = token.text();
+            text[text.rfind('\n').unwrap_or(0)..].to_string()
+        }
+        _ => " ".to_string(),
+    };
+
+    let next = match sorted {
+        true => entries.iter().position(|&i| {
+            let attrpath = children[i].as_node().and_then(|node| node.first_child());
+            children[i].kind() == NODE_ATTRPATH_VALUE
+                && attrpath.is_some_and(|attrpath| attrpath.to_string().as_str() > key)
+        }),
+        false => None,
+    };
+    let mut green_children: Vec<GreenElement> = children.iter().map(green).collect();
+    match (next, entries.last()) {
+        (Some(0), _) => {
+            let first = entries[0];
+            green_children.splice(first..first, [new_entry, whitespace(&separator(first))]);
+        }
+        (next, Some(&last)) => {
+            let prev = next.map_or(last, |next| entries[next - 1]);
+            let comment_len = match (children.get(prev + 1), children.get(prev + 2)) {
+                (Some(comment), _) if comment.kind() == TOKEN_COMMENT => 1,
+                (Some(space), Some(comment))
+                    if space.kind() == TOKEN_WHITESPACE
+                        && !space.to_string().contains('\n')
+                        && comment.kind() == TOKEN_COMMENT =>
+                {
+                    2
+                }
+                _ => 0,
+            };
+            let at = prev + 1 + comment_len;
+            let mut separator = separator(prev);
+            // A line comment extends to the end of the line, so the entry can't go on it
+            let line_comment = comment_len > 0 && children[at - 1].to_string().starts_with('#');
+            if line_comment && !separator.contains('\n') {
+                let root = set.ancestors().last().unwrap();
+                let indent = line_indent(&root, children[prev].text_range().start());
+                separator = format!("\n{}", indent);
+            }
+            green_children.splice(at..at, [whitespace(&separator), new_entry]);
+        }
+        (_, None) => {
+            let open = children.iter().position(|child| child.kind() == T!['{']);
+            let close = children.iter().rposition(|child| child.kind() == T!['}']);
+            let start = open.map_or(0, |open| open + 1);
+            let end = close.unwrap_or(children.len()).max(start);
+            let multiline = children[start..end]
+                .iter()
+                .any(|child| child.kind() == TOKEN_WHITESPACE && child.to_string().contains('\n'));
+            let inserted = if multiline {
+                let root = set.ancestors().last().unwrap();
+                let brace = open
+                    .map_or(set.text_range().start(), |open| children[open].text_range().start());
+                let indent = line_indent(&root, brace);
+                [
+                    whitespace(&format!("\n{}  ", indent)),
+                    new_entry,
+                    whitespace(&format!("\n{}", indent)),
+                ]
+            } else {
+                [whitespace(" "), new_entry, whitespace(" ")]
+            };
+            // Only whitespace is replaced, comments are kept before the entry
+            let comments = children[start..end]
+                .iter()
+                .filter(|child| child.kind() != TOKEN_WHITESPACE)
+                .map(green)
+                .collect::<Vec<_>>();
+            let mut replacement = Vec::new();
+            for comment in comments {
+                replacement.push(inserted[0].clone());
+                replacement.push(comment);
+            }
+            replacement.extend(inserted);
+            green_children.splice(start..end, replacement);
+        }
+    }
+
+    let green = GreenNode::new(NixLanguage::kind_to_raw(NODE_ATTR_SET), green_children);
+    Some(SyntaxNode::new_root(set.replace_with(green)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Root;
+
+    /// Insert into the innermost set of `s`
+    fn insert(s: &str, key: &str, value: &str, sorted: bool) -> String {
+        let root = Root::parse(s).ok().unwrap();
+        let set = root.syntax().descendants().filter_map(ast::AttrSet::cast).last().unwrap();
+        let new = insert_entry(&set, key, value, sorted).unwrap();
+        let reparsed = Root::parse(&new.to_string());
+        assert!(reparsed.errors().is_empty(), "{}", new);
+        assert_eq!(format!("{:#?}", reparsed.syntax()), format!("{:#?}", new));
+        new.to_string()
+    }
+
+    #[test]
+    fn indentation() {
+        let s = "{\n  foo = {\n    a = 1; # one\n\n    c = 3;\n  };\n}\n";
+        assert_eq!(
+            insert(s, "b", "2", true),
+            "{\n  foo = {\n    a = 1; # one\n    b = 2;\n\n    c = 3;\n  };\n}\n"
+        );
+        assert_eq!(
+            insert(s, "d", "[ ]", false),
+            "{\n  foo = {\n    a = 1; # one\n\n    c = 3;\n    d = [ ];\n  };\n}\n"
+        );
+        assert_eq!(
+            insert(s, "\"0\"", "0", true),
+            "{\n  foo = {\n    \"0\" = 0;\n    a = 1; # one\n\n    c = 3;\n  };\n}\n"
+        );
+    }
+
+    #[test]
+    fn single_line() {
+        assert_eq!(insert("{ b = 2; }", "a", "1", true), "{ a = 1; b = 2; }");
+        assert_eq!(insert("{ b = 2; }", "a", "1", false), "{ b = 2; a = 1; }");
+        assert_eq!(insert("rec { inherit x; }", "y.z", "x", true), "rec { inherit x; y.z = x; }");
+
+        // Not into a line comment after the previous entry
+        assert_eq!(insert("{ a = 1; # c\n}", "b", "2", false), "{ a = 1; # c\nb = 2;\n}");
+        assert_eq!(
+            insert("{\n  x = {\n    a = 1; # c\n  }; }", "b", "2", true),
+            "{\n  x = {\n    a = 1; # c\n    b = 2;\n  }; }"
+        );
+        assert_eq!(insert("{ a = 1; /* c */ }", "b", "2", false), "{ a = 1; /* c */ b = 2; }");
+    }
+
+    #[test]
+    fn invalid() {
+        let root = Root::parse("{ a = 1; }").ok().unwrap();
+        let set = ast::AttrSet::try_from(root.expr().unwrap()).unwrap();
+        for key in ["}", "a b", "a = 1; b", ""] {
+            assert!(insert_entry(&set, key, "1", true).is_none(), "{}", key);
+        }
+        assert!(insert_entry(&set, "b", "1; c = 2", true).is_none());
+        assert!(insert_entry(&set, "b", "2 # comment", true).is_none());
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(insert("{}", "a", "1", true), "{ a = 1; }");
+        assert_eq!(insert("{ }", "a", "1", true), "{ a = 1; }");
+        assert_eq!(
+            insert("let\n  x = {\n  };\nin x", "a", "1", true),
+            "let\n  x = {\n    a = 1;\n  };\nin x"
+        );
+        assert_eq!(
+            insert("{\n  # nothing yet\n}", "a", "1", false),
+            "{\n  # nothing yet\n  a = 1;\n}"
+        );
+    }
+}
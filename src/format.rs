@@ -0,0 +1,279 @@
+//! A formatter re-emitting syntax trees in a canonical style
+
+use crate::{
+    NodeOrToken, SyntaxElement,
+    SyntaxKind::{self, *},
+    SyntaxNode, SyntaxToken,
+};
+
+/// Format the tree of `root`. Attribute sets and `let` bindings get one
+/// binding per line with two spaces of indentation, lists are split into one
+/// element per line if they span multiple lines, and binary operators and `=`
+/// are surrounded by single spaces. Everything else keeps its line breaks, but
+/// runs of whitespace are collapsed. Comments are preserved and strings,
+/// paths and error nodes are left untouched.
+///
+/// Formatting only changes whitespace, so the result parses to the same tree,
+/// and formatting it again doesn't change it.
+pub fn format(root: &SyntaxNode) -> String {
+    let mut formatter = Formatter::default();
+    formatter.node(root);
+    let mut out = formatter.out;
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Separator {
+    None,
+    Space,
+    Newline { blank: bool },
+}
+
+#[derive(Default)]
+struct Formatter {
+    out: String,
+    /// Indentation of the items of the innermost block, in spaces
+    indent: usize,
+    /// Whether lines broken within the current binding are indented once more
+    hang: bool,
+    /// Whether the last thing written was a `#` comment, which must be
+    /// followed by a newline
+    after_line_comment: bool,
+}
+
+impl Formatter {
+    /// Indentation of the line currently being written
+    fn line_indent(&self) -> usize {
+        let line = &self.out[self.out.rfind('\n').map_or(0, |i| i + 1)..];
+        line.len() - line.trim_start_matches(' ').len()
+    }
+
+    fn newline(&mut self, indent: usize, blank: bool) {
+        self.out.truncate(self.out.trim_end_matches(' ').len());
+        self.out.push('\n');
+        if blank {
+            self.out.push('\n');
+        }
+        self.out.push_str(&" ".repeat(indent));
+        self.after_line_comment = false;
+    }
+
+    fn separate(&mut self, separator: Separator) {
+        let separator = match separator {
+            Separator::None | Separator::Space if self.after_line_comment => {
+                Separator::Newline { blank: false }
+            }
+            separator => separator,
+        };
+        match separator {
+            Separator::None => (),
+            Separator::Space => self.out.push(' '),
+            Separator::Newline { blank } => {
+                self.newline(self.indent + if self.hang { 2 } else { 0 }, blank)
+            }
+        }
+    }
+
+    fn write(&mut self, text: &str, last: Option<SyntaxToken>) {
+        self.out.push_str(text);
+        self.after_line_comment = last.is_some_and(|token| is_line_comment(&token));
+    }
+
+    fn element(&mut self, element: &SyntaxElement) {
+        match element {
+            NodeOrToken::Node(node) => self.node(node),
+            NodeOrToken::Token(token) => self.write(token.text(), Some(token.clone())),
+        }
+    }
+
+    fn node(&mut self, node: &SyntaxNode) {
+        match node.kind() {
+            NODE_STRING | NODE_PATH | NODE_ERROR => {
+                self.write(&node.to_string(), node.last_token())
+            }
+            NODE_ATTR_SET | NODE_LEGACY_LET => {
+                self.block(node.kind(), &mut node.children_with_tokens(), T!['{'], T!['}']);
+            }
+            NODE_LET_IN => {
+                let mut children = node.children_with_tokens();
+                let outer = self.line_indent();
+                if self.block(node.kind(), &mut children, T![let], T![in]) {
+                    self.newline(outer, false);
+                    self.elements(node.kind(), children);
+                }
+            }
+            NODE_LIST => self.list(node),
+            NODE_ATTRPATH_VALUE | NODE_INHERIT => {
+                let hang = std::mem::replace(&mut self.hang, true);
+                self.elements(node.kind(), node.children_with_tokens());
+                self.hang = hang;
+            }
+            kind => self.elements(kind, node.children_with_tokens()),
+        }
+    }
+
+    /// Write `elements`, keeping the line breaks between them but dropping
+    /// whitespace before the first one
+    fn elements(&mut self, parent: SyntaxKind, elements: impl Iterator<Item = SyntaxElement>) {
+        let mut prev = None;
+        let mut newlines = None;
+        for element in elements {
+            if element.kind() == TOKEN_WHITESPACE {
+                newlines = Some(count_newlines(&element));
+                continue;
+            }
+            if let Some(prev) = &prev {
+                self.separate(separator(parent, prev, &element, newlines));
+            }
+            self.element(&element);
+            prev = Some(element);
+            newlines = None;
+        }
+    }
+
+    /// Write the items between the `open` and `close` tokens of a block, one
+    /// per line, like the bindings of `{ a = 1; b = 2; }` or `let a = 1; in`.
+    /// Stops after writing `close` and returns whether there was one.
+    fn block(
+        &mut self,
+        parent: SyntaxKind,
+        children: &mut impl Iterator<Item = SyntaxElement>,
+        open: SyntaxKind,
+        close: SyntaxKind,
+    ) -> bool {
+        let outer = self.line_indent();
+        let mut prefix = Vec::new();
+        for child in children.by_ref() {
+            let done = child.kind() == open;
+            prefix.push(child);
+            if done {
+                break;
+            }
+        }
+        self.elements(parent, prefix.into_iter());
+
+        let (indent, hang) = (self.indent, self.hang);
+        self.indent = outer + 2;
+        self.hang = false;
+
+        let mut empty = true;
+        let mut closed = false;
+        let mut newlines = None;
+        for child in children.by_ref() {
+            if child.kind() == TOKEN_WHITESPACE {
+                newlines = Some(count_newlines(&child));
+                continue;
+            }
+            if child.kind() == close {
+                if empty {
+                    self.separate(Separator::Space);
+                } else {
+                    self.newline(outer, false);
+                }
+                self.element(&child);
+                closed = true;
+                break;
+            }
+
+            if !empty && child.kind() == TOKEN_COMMENT && newlines.unwrap_or(0) == 0 {
+                // Keep trailing comments on the line of the item they belong to
+                self.separate(Separator::Space);
+            } else {
+                self.newline(self.indent, !empty && newlines.unwrap_or(0) > 1);
+            }
+            self.element(&child);
+            empty = false;
+            newlines = None;
+        }
+
+        self.indent = indent;
+        self.hang = hang;
+        closed
+    }
+
+    /// Write a list on a single line, unless it or any of its elements spans
+    /// multiple lines or contains comments. Then write one element per line.
+    fn list(&mut self, node: &SyntaxNode) {
+        let outer = self.line_indent();
+        let mut multiline = node.text().contains_char('\n');
+
+        let mut items = Vec::new();
+        let mut close = None;
+        let mut newlines = 0;
+        for child in node.children_with_tokens() {
+            match child.kind() {
+                T!['['] => (),
+                T![']'] => close = Some(child),
+                TOKEN_WHITESPACE => newlines = count_newlines(&child),
+                kind => {
+                    // Format elements on their own, as if they were already on
+                    // their own line
+                    let mut formatter = Formatter {
+                        out: " ".repeat(outer + 2),
+                        indent: outer + 2,
+                        ..Formatter::default()
+                    };
+                    formatter.element(&child);
+                    let text = formatter.out.split_off(outer + 2);
+
+                    multiline |= kind == TOKEN_COMMENT || text.contains('\n');
+                    items.push((kind, text, formatter.after_line_comment, newlines));
+                    newlines = 0;
+                }
+            }
+        }
+
+        self.write("[", None);
+        for (i, (kind, text, after_line_comment, newlines)) in items.iter().enumerate() {
+            if !multiline || (i > 0 && *kind == TOKEN_COMMENT && *newlines == 0) {
+                self.separate(Separator::Space);
+            } else {
+                self.newline(outer + 2, i > 0 && *newlines > 1);
+            }
+            self.out.push_str(text);
+            self.after_line_comment = *after_line_comment;
+        }
+        if let Some(close) = close {
+            if multiline && !items.is_empty() {
+                self.newline(outer, false);
+            } else {
+                self.separate(Separator::Space);
+            }
+            self.element(&close);
+        }
+    }
+}
+
+/// The separator to write between the elements `prev` and `next` of `parent`,
+/// which had whitespace with `newlines` newlines between them, or `None` if
+/// they weren't separated at all.
+fn separator(
+    parent: SyntaxKind,
+    prev: &SyntaxElement,
+    next: &SyntaxElement,
+    newlines: Option<usize>,
+) -> Separator {
+    let is_operator = |element: &SyntaxElement| match parent {
+        NODE_BIN_OP => element.as_token().is_some_and(|token| !token.kind().is_trivia()),
+        NODE_ATTRPATH_VALUE => element.kind() == T![=],
+        _ => false,
+    };
+
+    match newlines {
+        _ if next.kind() == T![;] => Separator::None,
+        Some(n) if n > 0 => Separator::Newline { blank: n > 1 },
+        _ if is_operator(prev) || is_operator(next) => Separator::Space,
+        Some(_) => Separator::Space,
+        None => Separator::None,
+    }
+}
+
+fn count_newlines(element: &SyntaxElement) -> usize {
+    element.as_token().map_or(0, |token| token.text().matches('\n').count())
+}
+
+fn is_line_comment(token: &SyntaxToken) -> bool {
+    token.kind() == TOKEN_COMMENT && token.text().starts_with('#')
+}
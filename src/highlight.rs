@@ -0,0 +1,182 @@
+//! Classification of tokens for syntax highlighting
+
+use crate::{NodeOrToken, SyntaxKind::*, SyntaxNode, SyntaxToken, TextRange};
+
+/// What a token is, as far as highlighting is concerned
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HlTag {
+    Keyword,
+    Operator,
+    Number,
+    /// Strings, as well as paths and URIs
+    StringLit,
+    Comment,
+    /// Brackets, separators and the delimiters of interpolations
+    Punctuation,
+    /// A reference to one of the `BUILTINS`
+    BuiltinIdent,
+    /// A name being bound: an attribute key, an inherited name or a function
+    /// parameter
+    BindingName,
+    /// Any other identifier, including attribute names being selected
+    Variable,
+}
+
+/// The names that are in scope everywhere without importing anything. They
+/// are ordinary identifiers that might be shadowed, which isn't taken into
+/// account.
+pub const BUILTINS: &[&str] = &[
+    "abort",
+    "baseNameOf",
+    "builtins",
+    "derivation",
+    "dirOf",
+    "false",
+    "fetchTarball",
+    "import",
+    "isNull",
+    "map",
+    "null",
+    "placeholder",
+    "removeAttrs",
+    "scopedImport",
+    "throw",
+    "toString",
+    "true",
+];
+
+/// Classify every token of `root` in source order. Whitespace and tokens the
+/// tokenizer couldn't make sense of are left out.
+pub fn highlight(root: &SyntaxNode) -> Vec<(TextRange, HlTag)> {
+    root.descendants_with_tokens()
+        .filter_map(|element| match element {
+            NodeOrToken::Token(token) => Some((token.text_range(), tag(&token)?)),
+            NodeOrToken::Node(_) => None,
+        })
+        .collect()
+}
+
+fn tag(token: &SyntaxToken) -> Option<HlTag> {
+    let tag = match token.kind() {
+        TOKEN_WHITESPACE | TOKEN_ERROR => return None,
+        TOKEN_COMMENT => HlTag::Comment,
+        kind if kind.is_keyword() => HlTag::Keyword,
+        // `?` is an operator in `set ? attr`, but not in `{ name ? default }`
+        TOKEN_QUESTION if token.parent().is_none_or(|node| node.kind() != NODE_HAS_ATTR) => {
+            HlTag::Punctuation
+        }
+        kind if kind.is_operator() => HlTag::Operator,
+        TOKEN_INTEGER | TOKEN_FLOAT => HlTag::Number,
+        TOKEN_STRING_START | TOKEN_STRING_CONTENT | TOKEN_STRING_END | TOKEN_PATH | TOKEN_SPATH
+        | TOKEN_URI => HlTag::StringLit,
+        TOKEN_IDENT => ident_tag(token),
+        // Everything else is a bracket, separator or similar
+        _ => HlTag::Punctuation,
+    };
+    Some(tag)
+}
+
+fn ident_tag(token: &SyntaxToken) -> HlTag {
+    let ident = token.parent().filter(|node| node.kind() == NODE_IDENT);
+    let context = ident.as_ref().and_then(|ident| Some((ident, ident.parent()?)));
+    let Some((ident, parent)) = context else {
+        return HlTag::Variable;
+    };
+
+    let is_binding = match parent.kind() {
+        NODE_ATTRPATH => parent.parent().is_some_and(|node| node.kind() == NODE_ATTRPATH_VALUE),
+        NODE_INHERIT | NODE_IDENT_PARAM | NODE_PAT_BIND => true,
+        // The name of `name ? default`, not an identifier as the default
+        NODE_PAT_ENTRY => parent.first_child().as_ref() == Some(ident),
+        _ => false,
+    };
+    if is_binding {
+        HlTag::BindingName
+    } else if parent.kind() == NODE_ATTRPATH {
+        HlTag::Variable
+    } else if BUILTINS.contains(&token.text()) {
+        HlTag::BuiltinIdent
+    } else {
+        HlTag::Variable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Root;
+
+    fn highlight(s: &str) -> Vec<(&str, HlTag)> {
+        let root = Root::parse(s).syntax();
+        super::highlight(&root)
+            .into_iter()
+            .map(|(range, tag)| (&s[std::ops::Range::<usize>::from(range)], tag))
+            .collect()
+    }
+
+    #[test]
+    fn program() {
+        use HlTag::*;
+
+        let s = "{ pkgs ? import <nixpkgs> {}, ... }: # comment\n\
+                 let inherit (pkgs) lib; in\n\
+                 { x = lib.map (y: y + 1) [ (pkgs ? lib) true ]; s = \"a${lib}\"; }";
+        assert_eq!(
+            highlight(s),
+            [
+                ("{", Punctuation),
+                ("pkgs", BindingName),
+                ("?", Punctuation),
+                ("import", BuiltinIdent),
+                ("<nixpkgs>", StringLit),
+                ("{", Punctuation),
+                ("}", Punctuation),
+                (",", Punctuation),
+                ("...", Punctuation),
+                ("}", Punctuation),
+                (":", Punctuation),
+                ("# comment", Comment),
+                ("let", Keyword),
+                ("inherit", Keyword),
+                ("(", Punctuation),
+                ("pkgs", Variable),
+                (")", Punctuation),
+                ("lib", BindingName),
+                (";", Punctuation),
+                ("in", Keyword),
+                ("{", Punctuation),
+                ("x", BindingName),
+                ("=", Punctuation),
+                ("lib", Variable),
+                (".", Punctuation),
+                ("map", Variable),
+                ("(", Punctuation),
+                ("y", BindingName),
+                (":", Punctuation),
+                ("y", Variable),
+                ("+", Operator),
+                ("1", Number),
+                (")", Punctuation),
+                ("[", Punctuation),
+                ("(", Punctuation),
+                ("pkgs", Variable),
+                ("?", Operator),
+                ("lib", Variable),
+                (")", Punctuation),
+                ("true", BuiltinIdent),
+                ("]", Punctuation),
+                (";", Punctuation),
+                ("s", BindingName),
+                ("=", Punctuation),
+                ("\"", StringLit),
+                ("a", StringLit),
+                ("${", Punctuation),
+                ("lib", Variable),
+                ("}", Punctuation),
+                ("\"", StringLit),
+                (";", Punctuation),
+                ("}", Punctuation),
+            ]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Conversion of syntax trees to JSON, for consuming them from other languages
+
+use serde_json::{json, Value};
+
+use crate::{NodeOrToken, SyntaxElement, SyntaxNode};
+
+/// Convert `node` and all of its descendants to JSON. Nodes look like
+/// `{ "kind": "NODE_ATTR_SET", "range": [0, 12], "children": [...] }`, tokens
+/// like `{ "kind": "TOKEN_IDENT", "range": [2, 3], "text": "a" }`. Ranges are
+/// the byte offsets of the `TextRange` of each element.
+///
+/// If `trivia` is true, whitespace and comments are kept, so the source can
+/// be recovered by concatenating the text of all tokens.
+pub fn to_json(node: &SyntaxNode, trivia: bool) -> Value {
+    element_to_json(NodeOrToken::Node(node.clone()), trivia)
+}
+
+fn element_to_json(element: SyntaxElement, trivia: bool) -> Value {
+    let range = element.text_range();
+    let range = [u32::from(range.start()), u32::from(range.end())];
+    let kind = format!("{:?}", element.kind());
+    match element {
+        NodeOrToken::Node(node) => {
+            let children: Vec<Value> = node
+                .children_with_tokens()
+                .filter(|child| trivia || !child.kind().is_trivia())
+                .map(|child| element_to_json(child, trivia))
+                .collect();
+            json!({ "kind": kind, "range": range, "children": children })
+        }
+        NodeOrToken::Token(token) => json!({ "kind": kind, "range": range, "text": token.text() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::to_json;
+    use crate::Root;
+
+    #[test]
+    fn attrset() {
+        let parse = Root::parse("{ a = 1; }");
+        assert_eq!(
+            to_json(&parse.syntax(), false),
+            json!({ "kind": "NODE_ROOT", "range": [0, 10], "children": [
+                { "kind": "NODE_ATTR_SET", "range": [0, 10], "children": [
+                    { "kind": "TOKEN_L_BRACE", "range": [0, 1], "text": "{" },
+                    { "kind": "NODE_ATTRPATH_VALUE", "range": [2, 8], "children": [
+                        { "kind": "NODE_ATTRPATH", "range": [2, 3], "children": [
+                            { "kind": "NODE_IDENT", "range": [2, 3], "children": [
+                                { "kind": "TOKEN_IDENT", "range": [2, 3], "text": "a" },
+                            ] },
+                        ] },
+                        { "kind": "TOKEN_ASSIGN", "range": [4, 5], "text": "=" },
+                        { "kind": "NODE_LITERAL", "range": [6, 7], "children": [
+                            { "kind": "TOKEN_INTEGER", "range": [6, 7], "text": "1" },
+                        ] },
+                        { "kind": "TOKEN_SEMICOLON", "range": [7, 8], "text": ";" },
+                    ] },
+                    { "kind": "TOKEN_R_BRACE", "range": [9, 10], "text": "}" },
+                ] },
+            ] })
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        fn text(value: &serde_json::Value, out: &mut String) {
+            match value.get("text") {
+                Some(t) => out.push_str(t.as_str().unwrap()),
+                None => value["children"].as_array().unwrap().iter().for_each(|c| text(c, out)),
+            }
+        }
+
+        let input = "# comment\nlet\n  a = 1; /* b */\nin a\n";
+        let mut out = String::new();
+        text(&to_json(&Root::parse(input).syntax(), true), &mut out);
+        assert_eq!(out, input);
+    }
+}
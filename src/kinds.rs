@@ -1,116 +1,210 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[repr(u16)]
-#[allow(non_camel_case_types)]
-pub enum SyntaxKind {
-    // Internals
-    TOKEN_COMMENT,
-    TOKEN_ERROR,
-    TOKEN_WHITESPACE,
-
-    // Keywords
-    TOKEN_ASSERT,
-    TOKEN_ELSE,
-    TOKEN_IF,
-    TOKEN_IN,
-    TOKEN_INHERIT,
-    TOKEN_LET,
-    TOKEN_OR,
-    TOKEN_REC,
-    TOKEN_THEN,
-    TOKEN_WITH,
-
-    // Symbols
-    TOKEN_L_BRACE,
-    TOKEN_R_BRACE,
-    TOKEN_L_BRACK,
-    TOKEN_R_BRACK,
-    TOKEN_ASSIGN,
-    TOKEN_AT,
-    TOKEN_COLON,
-    TOKEN_COMMA,
-    TOKEN_DOT,
-    TOKEN_ELLIPSIS,
-    TOKEN_QUESTION,
-    TOKEN_SEMICOLON,
-
-    // Operators
-    TOKEN_L_PAREN,
-    TOKEN_R_PAREN,
-    TOKEN_CONCAT,
-    TOKEN_INVERT,
-    TOKEN_UPDATE,
-
-    TOKEN_ADD,
-    TOKEN_SUB,
-    TOKEN_MUL,
-    TOKEN_DIV,
-
-    TOKEN_AND_AND,
-    TOKEN_EQUAL,
-    TOKEN_IMPLICATION,
-    TOKEN_LESS,
-    TOKEN_LESS_OR_EQ,
-    TOKEN_MORE,
-    TOKEN_MORE_OR_EQ,
-    TOKEN_NOT_EQUAL,
-    TOKEN_OR_OR,
-    TOKEN_PIPE_RIGHT,
-    TOKEN_PIPE_LEFT,
-
-    // Identifiers and values
-    TOKEN_FLOAT,
-    TOKEN_IDENT,
-    TOKEN_INTEGER,
-    TOKEN_INTERPOL_END,
-    TOKEN_INTERPOL_START,
-    TOKEN_PATH,
-    TOKEN_URI,
-    TOKEN_STRING_CONTENT,
-    TOKEN_STRING_END,
-    TOKEN_STRING_START,
-
-    NODE_APPLY,
-    NODE_ASSERT,
-    NODE_ATTRPATH,
-    NODE_DYNAMIC,
-    NODE_ERROR,
-    NODE_IDENT,
-    NODE_IF_ELSE,
-    NODE_SELECT,
-    NODE_INHERIT,
-    NODE_INHERIT_FROM,
-    NODE_STRING,
-    NODE_INTERPOL,
-    NODE_LAMBDA,
-    NODE_IDENT_PARAM,
-    // An old let { x = 92; body = x; } syntax
-    NODE_LEGACY_LET,
-    NODE_LET_IN,
-    NODE_LIST,
-    NODE_BIN_OP,
-    NODE_PAREN,
-    NODE_PATTERN,
-    NODE_PAT_BIND,
-    NODE_PAT_ENTRY,
-    NODE_ROOT,
-    NODE_ATTR_SET,
-    NODE_ATTRPATH_VALUE,
-    NODE_UNARY_OP,
-    NODE_LITERAL,
-    NODE_WITH,
-    NODE_PATH,
-    // Attrpath existence check: foo ? bar.${baz}."bux"
-    NODE_HAS_ATTR,
-
-    #[doc(hidden)]
-    __LAST,
+/// Declare `SyntaxKind` with a hidden `__LAST` variant after the listed ones,
+/// and the `NAMES` and `KINDS` of all variants but `__LAST` in the same order,
+/// so that `KINDS[kind as usize]` is `kind` and `NAMES[kind as usize]` its name
+macro_rules! syntax_kinds {
+    (
+        $(#[$attr:meta])*
+        pub enum SyntaxKind {
+            $($kind:ident,)*
+        }
+    ) => {
+        $(#[$attr])*
+        // `__LAST` is there to count the kinds, not to make matching them non-exhaustive
+        #[allow(clippy::manual_non_exhaustive)]
+        pub enum SyntaxKind {
+            $($kind,)*
+            #[doc(hidden)]
+            __LAST,
+        }
+
+        /// The names of all kinds below `__LAST`, in the order they're declared in
+        const NAMES: &[&str] = &[$(stringify!($kind)),*];
+
+        /// All kinds below `__LAST`, in the order they're declared in
+        const KINDS: &[SyntaxKind] = &[$(SyntaxKind::$kind),*];
+    };
+}
+
+syntax_kinds! {
+    /// The kind of a token or node in a syntax tree.
+    ///
+    /// Green trees store kinds as their `u16` discriminant, which is the position
+    /// of the variant in this list and is converted back by `from_raw`. Adding a
+    /// kind can shift the discriminants of the ones after it, so they aren't
+    /// stable between releases: to store kinds outside of a running program, use
+    /// `name` and `from_name` instead.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #[repr(u16)]
+    #[allow(non_camel_case_types)]
+    pub enum SyntaxKind {
+        // Internals
+        TOKEN_COMMENT,
+        TOKEN_ERROR,
+        TOKEN_WHITESPACE,
+
+        // Keywords
+        TOKEN_ASSERT,
+        TOKEN_ELSE,
+        TOKEN_IF,
+        TOKEN_IN,
+        TOKEN_INHERIT,
+        TOKEN_LET,
+        TOKEN_OR,
+        TOKEN_REC,
+        TOKEN_THEN,
+        TOKEN_WITH,
+
+        // Symbols
+        TOKEN_L_BRACE,
+        TOKEN_R_BRACE,
+        TOKEN_L_BRACK,
+        TOKEN_R_BRACK,
+        TOKEN_ASSIGN,
+        TOKEN_AT,
+        TOKEN_COLON,
+        TOKEN_COMMA,
+        TOKEN_DOT,
+        TOKEN_ELLIPSIS,
+        TOKEN_QUESTION,
+        TOKEN_SEMICOLON,
+
+        // Operators
+        TOKEN_L_PAREN,
+        TOKEN_R_PAREN,
+        TOKEN_CONCAT,
+        TOKEN_INVERT,
+        TOKEN_UPDATE,
+
+        TOKEN_ADD,
+        TOKEN_SUB,
+        TOKEN_MUL,
+        TOKEN_DIV,
+
+        TOKEN_AND_AND,
+        TOKEN_EQUAL,
+        TOKEN_IMPLICATION,
+        TOKEN_LESS,
+        TOKEN_LESS_OR_EQ,
+        TOKEN_MORE,
+        TOKEN_MORE_OR_EQ,
+        TOKEN_NOT_EQUAL,
+        TOKEN_OR_OR,
+        TOKEN_PIPE_RIGHT,
+        TOKEN_PIPE_LEFT,
+
+        // Identifiers and values
+        TOKEN_FLOAT,
+        TOKEN_IDENT,
+        TOKEN_INTEGER,
+        TOKEN_INTERPOL_END,
+        TOKEN_INTERPOL_START,
+        TOKEN_PATH,
+        // A search path like <nixpkgs/lib>
+        TOKEN_SPATH,
+        TOKEN_URI,
+        TOKEN_STRING_CONTENT,
+        TOKEN_STRING_END,
+        TOKEN_STRING_START,
+
+        NODE_APPLY,
+        NODE_ASSERT,
+        NODE_ATTRPATH,
+        NODE_DYNAMIC,
+        NODE_ERROR,
+        NODE_IDENT,
+        NODE_IF_ELSE,
+        NODE_SELECT,
+        NODE_INHERIT,
+        NODE_INHERIT_FROM,
+        NODE_STRING,
+        NODE_INTERPOL,
+        NODE_LAMBDA,
+        NODE_IDENT_PARAM,
+        // An old let { x = 92; body = x; } syntax
+        NODE_LEGACY_LET,
+        NODE_LET_IN,
+        NODE_LIST,
+        NODE_BIN_OP,
+        NODE_PAREN,
+        NODE_PATTERN,
+        NODE_PAT_BIND,
+        NODE_PAT_ENTRY,
+        NODE_ROOT,
+        NODE_ATTR_SET,
+        NODE_ATTRPATH_VALUE,
+        NODE_UNARY_OP,
+        NODE_LITERAL,
+        NODE_WITH,
+        NODE_PATH,
+        // Attrpath existence check: foo ? bar.${baz}."bux"
+        NODE_HAS_ATTR,
+
+    }
 }
+
 use SyntaxKind::*;
 
+/// The categories the `is_*` methods of `SyntaxKind` are based on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Category {
+    Trivia,
+    Error,
+    Keyword,
+    Punctuation,
+    Operator,
+    Literal,
+    Ident,
+    String,
+    Node,
+}
+
 impl SyntaxKind {
-    /// Returns true if this token is a literal, such as an integer or a string
+    // This is deliberately exhaustive so that new kinds have to be categorized
+    fn category(self) -> Category {
+        match self {
+            TOKEN_COMMENT | TOKEN_WHITESPACE => Category::Trivia,
+            TOKEN_ERROR => Category::Error,
+            TOKEN_ASSERT | TOKEN_ELSE | TOKEN_IF | TOKEN_IN | TOKEN_INHERIT | TOKEN_LET
+            | TOKEN_OR | TOKEN_REC | TOKEN_THEN | TOKEN_WITH => Category::Keyword,
+            TOKEN_L_BRACE | TOKEN_R_BRACE | TOKEN_L_BRACK | TOKEN_R_BRACK | TOKEN_L_PAREN
+            | TOKEN_R_PAREN | TOKEN_ASSIGN | TOKEN_AT | TOKEN_COLON | TOKEN_COMMA | TOKEN_DOT
+            | TOKEN_ELLIPSIS | TOKEN_SEMICOLON | TOKEN_INTERPOL_START | TOKEN_INTERPOL_END => {
+                Category::Punctuation
+            }
+            TOKEN_QUESTION | TOKEN_CONCAT | TOKEN_INVERT | TOKEN_UPDATE | TOKEN_ADD | TOKEN_SUB
+            | TOKEN_MUL | TOKEN_DIV | TOKEN_AND_AND | TOKEN_EQUAL | TOKEN_IMPLICATION
+            | TOKEN_LESS | TOKEN_LESS_OR_EQ | TOKEN_MORE | TOKEN_MORE_OR_EQ | TOKEN_NOT_EQUAL
+            | TOKEN_OR_OR | TOKEN_PIPE_RIGHT | TOKEN_PIPE_LEFT => Category::Operator,
+            TOKEN_FLOAT | TOKEN_INTEGER | TOKEN_PATH | TOKEN_SPATH | TOKEN_URI => Category::Literal,
+            TOKEN_IDENT => Category::Ident,
+            TOKEN_STRING_CONTENT | TOKEN_STRING_END | TOKEN_STRING_START => Category::String,
+            NODE_APPLY | NODE_ASSERT | NODE_ATTRPATH | NODE_DYNAMIC | NODE_ERROR | NODE_IDENT
+            | NODE_IF_ELSE | NODE_SELECT | NODE_INHERIT | NODE_INHERIT_FROM | NODE_STRING
+            | NODE_INTERPOL | NODE_LAMBDA | NODE_IDENT_PARAM | NODE_LEGACY_LET | NODE_LET_IN
+            | NODE_LIST | NODE_BIN_OP | NODE_PAREN | NODE_PATTERN | NODE_PAT_BIND
+            | NODE_PAT_ENTRY | NODE_ROOT | NODE_ATTR_SET | NODE_ATTRPATH_VALUE | NODE_UNARY_OP
+            | NODE_LITERAL | NODE_WITH | NODE_PATH | NODE_HAS_ATTR | __LAST => Category::Node,
+        }
+    }
+
+    /// Returns true if this token is a literal: an integer, float, path, search
+    /// path or URI.
+    /// Strings consist of several tokens and aren't included.
     pub fn is_literal(self) -> bool {
-        matches!(self, TOKEN_FLOAT | TOKEN_INTEGER | TOKEN_PATH | TOKEN_URI)
+        self.category() == Category::Literal
+    }
+
+    /// Returns true if this token is a keyword, including the `or` of
+    /// `a.b or c`, which can also be used as an identifier
+    pub fn is_keyword(self) -> bool {
+        self.category() == Category::Keyword
+    }
+
+    /// Returns true if this token is a unary or binary operator like `!` or
+    /// `+`. This includes the `?` of `set ? attr`, even though the same token
+    /// is used for default arguments, but not the `.` of `set.attr`.
+    pub fn is_operator(self) -> bool {
+        self.category() == Category::Operator
     }
 
     /// Returns true if this token should be used as a function argument.
@@ -133,6 +227,100 @@ impl SyntaxKind {
     /// Returns true if this token is a comment, whitespace, or similar, and
     /// should be skipped over by the parser.
     pub fn is_trivia(self) -> bool {
-        matches!(self, TOKEN_COMMENT | TOKEN_WHITESPACE)
+        self.category() == Category::Trivia
+    }
+
+    /// The name of the variant, like `"NODE_ATTR_SET"`
+    pub fn name(self) -> &'static str {
+        match self {
+            __LAST => "__LAST",
+            _ => NAMES[self as usize],
+        }
+    }
+
+    /// The kind with the given variant name, the inverse of `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let index = NAMES.iter().position(|&other| other == name)?;
+        Some(KINDS[index])
+    }
+
+    /// The kind with the discriminant `raw`, or `None` if there is none. The
+    /// hidden `__LAST` isn't a kind of anything, so it's `None` too.
+    pub fn from_raw(raw: u16) -> Option<Self> {
+        KINDS.get(usize::from(raw)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rowan::Language;
+
+    use super::*;
+    use crate::NixLanguage;
+
+    #[test]
+    fn categories() {
+        assert!(TOKEN_WHITESPACE.is_trivia() && TOKEN_COMMENT.is_trivia());
+        assert!(TOKEN_IF.is_keyword() && TOKEN_OR.is_keyword());
+        assert!(TOKEN_INTEGER.is_literal() && TOKEN_URI.is_literal());
+        assert!(TOKEN_ADD.is_operator() && TOKEN_INVERT.is_operator());
+
+        for kind in [TOKEN_IDENT, TOKEN_L_PAREN, TOKEN_STRING_START, TOKEN_ERROR, NODE_LITERAL] {
+            assert!(!kind.is_trivia());
+            assert!(!kind.is_keyword());
+            assert!(!kind.is_literal());
+            assert!(!kind.is_operator());
+        }
+    }
+
+    /// Moving kinds around changes how green trees are interpreted, so it
+    /// should only be done on purpose
+    #[test]
+    fn discriminants() {
+        let kinds = [
+            (TOKEN_COMMENT, 0),
+            (TOKEN_WHITESPACE, 2),
+            (TOKEN_ASSERT, 3),
+            (TOKEN_IDENT, 46),
+            (TOKEN_PATH, 50),
+            (TOKEN_SPATH, 51),
+            (TOKEN_STRING_START, 55),
+            (NODE_APPLY, 56),
+            (NODE_ROOT, 78),
+            (NODE_HAS_ATTR, 85),
+            (__LAST, 86),
+        ];
+        for (kind, discriminant) in kinds {
+            assert_eq!(kind as u16, discriminant, "{:?}", kind);
+        }
+        for raw in 0..__LAST as u16 {
+            assert_eq!(SyntaxKind::from_raw(raw).map(|kind| kind as u16), Some(raw));
+        }
+    }
+
+    #[test]
+    fn out_of_range() {
+        for raw in [__LAST as u16, __LAST as u16 + 1, 1000, u16::MAX] {
+            assert_eq!(SyntaxKind::from_raw(raw), None);
+            assert_eq!(NixLanguage::kind_from_raw(rowan::SyntaxKind(raw)), TOKEN_ERROR);
+        }
+
+        // A green tree built with a kind that doesn't exist
+        let token = rowan::GreenToken::new(rowan::SyntaxKind(u16::MAX), "x");
+        let green = rowan::GreenNode::new(NixLanguage::kind_to_raw(NODE_ROOT), [token.into()]);
+        let root = crate::SyntaxNode::new_root(green);
+        assert_eq!(root.first_token().unwrap().kind(), TOKEN_ERROR);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(NAMES.len(), __LAST as usize);
+        for raw in 0..__LAST as u16 {
+            let kind = NixLanguage::kind_from_raw(rowan::SyntaxKind(raw));
+            assert_eq!(kind.name(), format!("{:?}", kind));
+            assert_eq!(SyntaxKind::from_name(kind.name()), Some(kind));
+        }
+        assert_eq!(SyntaxKind::from_name("__LAST"), None);
+        assert_eq!(SyntaxKind::from_name("attr_set"), None);
     }
 }
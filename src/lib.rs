@@ -1,34 +1,58 @@
 #[macro_use]
 mod macros;
 pub mod ast;
+pub mod builder;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostic;
+pub mod edit;
+pub mod format;
+pub mod highlight;
+#[cfg(feature = "serde")]
+pub mod json;
 mod kinds;
+mod line_index;
+pub mod lint;
 pub mod parser;
+mod reparsing;
+pub mod scope;
 #[cfg(test)]
 mod tests;
 mod token_set;
 pub mod tokenizer;
+pub mod visit;
 
-use std::marker::PhantomData;
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt::Write,
+    hash::Hasher,
+    marker::PhantomData,
+    sync::{Arc, OnceLock},
+};
 
-pub use self::{kinds::SyntaxKind, tokenizer::tokenize};
+pub use self::{
+    kinds::SyntaxKind,
+    line_index::{utf16_to_utf8, utf8_to_utf16, ColumnEncoding, LineIndex},
+    reparsing::TextEdit,
+    tokenizer::{tokenize, tokens},
+};
 
 use ast::AstNode;
-use parser::ParseError;
-use rowan::GreenNode;
-pub use rowan::{NodeOrToken, TextRange, TextSize, TokenAtOffset, WalkEvent};
+use parser::{ParseConfig, ParseError};
+pub use rowan::{
+    Direction, GreenNode, NodeCache, NodeOrToken, TextRange, TextSize, TokenAtOffset, WalkEvent,
+};
+use rowan::{GreenNodeData, GreenToken, GreenTokenData, Language};
 pub(crate) use token_set::TokenSet;
 
-use self::tokenizer::Tokenizer;
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum NixLanguage {}
 
 impl rowan::Language for NixLanguage {
     type Kind = SyntaxKind;
+    /// Kinds that don't exist, which only occur in green trees not built by
+    /// this crate, become `TOKEN_ERROR`
     fn kind_from_raw(raw: rowan::SyntaxKind) -> Self::Kind {
-        let discriminant: u16 = raw.0;
-        assert!(discriminant <= (SyntaxKind::__LAST as u16));
-        unsafe { std::mem::transmute::<u16, SyntaxKind>(discriminant) }
+        SyntaxKind::from_raw(raw.0).unwrap_or(SyntaxKind::TOKEN_ERROR)
     }
     fn kind_to_raw(kind: Self::Kind) -> rowan::SyntaxKind {
         rowan::SyntaxKind(kind as u16)
@@ -45,39 +69,302 @@ pub use ast::Root;
 
 impl Root {
     pub fn parse(s: &str) -> Parse<Root> {
-        let (green, errors) = parser::parse(Tokenizer::new(s));
-        Parse { green, errors, _ty: PhantomData }
+        Self::parse_with_config(s, ParseConfig::default())
+    }
+
+    /// Parse `s`, allowing expressions to be nested at most `max_depth`
+    /// levels deep. Deeper input is reported as `ParseError::DepthLimitExceeded`
+    /// instead of overflowing the stack.
+    pub fn parse_with_limit(s: &str, max_depth: usize) -> Parse<Root> {
+        Self::parse_with_config(s, ParseConfig { max_depth, ..Default::default() })
+    }
+
+    /// Parse `s` with the given options
+    pub fn parse_with_config(s: &str, config: ParseConfig) -> Parse<Root> {
+        let (green, errors) = parser::parse_text(s, config, None);
+        Parse { config, ..Parse::from_parts(green, errors) }
+    }
+
+    /// Parse `s`, sharing identical nodes and tokens with the trees parsed
+    /// before with the same `cache`. This saves memory when many files stay
+    /// parsed at once, since code like `lib.mkOption` is repeated throughout
+    /// them. Only tokens and small nodes are deduplicated.
+    ///
+    /// The cache is borrowed mutably, so it can only be used by one parse at
+    /// a time. To parse in parallel, give every thread its own cache, or
+    /// share one behind a `Mutex` at the price of parsing one file at a time.
+    /// The resulting green trees are `Send` and `Sync` either way. Trees
+    /// parsed with any `config` can share the same cache.
+    pub fn parse_with_cache(s: &str, config: ParseConfig, cache: &mut NodeCache) -> Parse<Root> {
+        let (green, errors) = parser::parse_text(s, config, Some(cache));
+        Parse { config, ..Parse::from_parts(green, errors) }
+    }
+
+    /// Parse a file given as bytes, which must be UTF-8. A leading byte order
+    /// mark is skipped and not part of the tree, so all ranges are relative
+    /// to the source after it.
+    pub fn parse_bytes(bytes: &[u8]) -> Result<Parse<Root>, EncodingError> {
+        let bom = if bytes.starts_with(b"\xEF\xBB\xBF") { 3 } else { 0 };
+        match std::str::from_utf8(&bytes[bom..]) {
+            Ok(s) => Ok(Self::parse(s)),
+            Err(error) => Err(EncodingError { offset: bom + error.valid_up_to() }),
+        }
+    }
+}
+
+/// The error returned by `Root::parse_bytes` if a file isn't valid UTF-8
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodingError {
+    /// The offset of the first invalid byte within all bytes given, including
+    /// a byte order mark
+    pub offset: usize,
+}
+
+impl std::fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid UTF-8 at byte {}", self.offset)
     }
 }
 
+impl std::error::Error for EncodingError {}
+
+/// Parse `s` as a single expression instead of a whole file. The tree is
+/// the same as with `Root::parse`, with a `NODE_ROOT` that holds the
+/// expression and the whitespace and comments around it, so that accessors
+/// looking at the tokens before a node, like `doc_comment`, work the same.
+/// `tree` returns the expression instead of the root. If anything besides
+/// trivia follows the expression, it's reported as
+/// `ParseError::UnexpectedExtra` and the expression is an error node
+/// containing both.
+pub fn parse_expr(s: &str) -> Parse<ast::Expr> {
+    let Parse { green, errors, .. } = Root::parse(s);
+    let owned = |child: NodeOrToken<&GreenNodeData, &GreenTokenData>| match child {
+        NodeOrToken::Node(node) => NodeOrToken::Node(node.to_owned()),
+        NodeOrToken::Token(token) => NodeOrToken::Token(token.to_owned()),
+    };
+    let mut children: Vec<NodeOrToken<GreenNode, GreenToken>> =
+        green.children().map(owned).collect();
+
+    // Everything from the first to the last node, or all of it without any
+    let is_node = |child: &NodeOrToken<_, _>| child.as_node().is_some();
+    let (first, last) =
+        match (children.iter().position(is_node), children.iter().rposition(is_node)) {
+            (Some(first), Some(last)) => (first, last + 1),
+            _ => (0, children.len()),
+        };
+    let green = if last - first == 1 && is_node(&children[first]) {
+        green
+    } else {
+        let error: Vec<_> = children.drain(first..last).collect();
+        let error = GreenNode::new(NixLanguage::kind_to_raw(SyntaxKind::NODE_ERROR), error);
+        children.insert(first, NodeOrToken::Node(error));
+        GreenNode::new(NixLanguage::kind_to_raw(SyntaxKind::NODE_ROOT), children)
+    };
+    Parse::from_expr_parts(green, errors)
+}
+
+/// Whether the trees of `a` and `b` are the same when ignoring whitespace and
+/// comments: they have nodes of the same kinds nested the same way, and all
+/// other tokens have the same kinds and text. Whitespace within strings is
+/// part of their content and not ignored.
+pub fn structural_eq(a: &SyntaxNode, b: &SyntaxNode) -> bool {
+    let mut a = structural_events(a);
+    let mut b = structural_events(b);
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return true,
+            (Some(WalkEvent::Enter(a)), Some(WalkEvent::Enter(b))) => {
+                let same = match (a, b) {
+                    (NodeOrToken::Node(a), NodeOrToken::Node(b)) => a.kind() == b.kind(),
+                    (NodeOrToken::Token(a), NodeOrToken::Token(b)) => {
+                        a.kind() == b.kind() && a.text() == b.text()
+                    }
+                    _ => false,
+                };
+                if !same {
+                    return false;
+                }
+            }
+            (Some(WalkEvent::Leave(_)), Some(WalkEvent::Leave(_))) => (),
+            _ => return false,
+        }
+    }
+}
+
+/// A hash of the tree of `node` consistent with `structural_eq`: nodes that
+/// are structurally equal have the same hash, wherever they are in the file.
+/// This makes it usable as a cheap key for caching results per subtree. The
+/// hash is the same across runs, but not necessarily across versions.
+pub fn structural_hash(node: &SyntaxNode) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for event in structural_events(node) {
+        match event {
+            WalkEvent::Enter(NodeOrToken::Node(node)) => {
+                hasher.write_u8(0);
+                hasher.write_u16(node.kind() as u16);
+            }
+            WalkEvent::Enter(NodeOrToken::Token(token)) => {
+                hasher.write_u8(1);
+                hasher.write_u16(token.kind() as u16);
+                hasher.write(token.text().as_bytes());
+                hasher.write_u8(0xff);
+            }
+            WalkEvent::Leave(_) => hasher.write_u8(2),
+        }
+    }
+    hasher.finish()
+}
+
+/// The elements of the tree of `node` without trivia, which is ignored by
+/// `structural_eq` and `structural_hash`
+fn structural_events(node: &SyntaxNode) -> impl Iterator<Item = WalkEvent<SyntaxElement>> {
+    node.preorder_with_tokens().filter(|event| match event {
+        WalkEvent::Enter(element) | WalkEvent::Leave(element) => !element.kind().is_trivia(),
+    })
+}
+
 /// The result of a parse
 #[derive(Clone)]
 pub struct Parse<T> {
     green: GreenNode,
     errors: Vec<ParseError>,
+    config: ParseConfig,
+    /// Whether the tree is the only child of the root instead of the root
+    /// itself, see `parse_expr`
+    fragment: bool,
+    line_index: Arc<OnceLock<LineIndex>>,
     _ty: PhantomData<fn() -> T>,
 }
 
 impl<T> Parse<T> {
+    /// Put a parse back together from its tree and errors, for example after
+    /// storing them in a cache. Nothing is checked: the errors should be the
+    /// ones of the tree, and the root of the tree should be a `T`, otherwise
+    /// methods like `tree` panic. Use `cast` to check the latter. The tree is
+    /// assumed to be parsed by `Root::parse` with the default `ParseConfig`.
+    /// For the parts of a `parse_expr` result, use `from_expr_parts`.
+    pub fn from_parts(green: GreenNode, errors: Vec<ParseError>) -> Self {
+        Parse {
+            green,
+            errors,
+            config: ParseConfig::default(),
+            fragment: false,
+            line_index: Arc::default(),
+            _ty: PhantomData,
+        }
+    }
+
+    /// Like `from_parts`, for the tree and errors of a `parse_expr` result,
+    /// whose `tree` is the single node below the root instead of the root.
+    /// The root should have exactly one child node for that, like the roots
+    /// of `parse_expr` do.
+    pub fn from_expr_parts(green: GreenNode, errors: Vec<ParseError>) -> Self {
+        Parse { fragment: true, ..Parse::from_parts(green, errors) }
+    }
+
+    /// The options the tree was parsed with, which `reparse` parses with again
+    pub fn config(&self) -> ParseConfig {
+        self.config
+    }
+
+    /// The green node of the root of the tree, for building additional roots
+    /// with `SyntaxNode::new_root` without reparsing. Green nodes are immutable
+    /// and reference counted, so clones of a `Parse` all share the same one.
+    pub fn green(&self) -> &GreenNode {
+        &self.green
+    }
+
     pub fn syntax(&self) -> SyntaxNode {
         SyntaxNode::new_root(self.green.clone())
     }
+
+    /// The text that was parsed. The tree is lossless, so this is always
+    /// exactly the input, even if it contains errors.
+    pub fn source(&self) -> String {
+        self.green.to_string()
+    }
+
+    /// The same parse as another type of node, or `None` if the tree isn't
+    /// one. This doesn't copy anything.
+    pub fn cast<U: AstNode>(self) -> Option<Parse<U>> {
+        U::can_cast(self.tree_node().kind()).then_some(Parse {
+            green: self.green,
+            errors: self.errors,
+            config: self.config,
+            fragment: self.fragment,
+            line_index: self.line_index,
+            _ty: PhantomData,
+        })
+    }
+
+    /// The node `tree` returns
+    fn tree_node(&self) -> SyntaxNode {
+        let root = self.syntax();
+        match self.fragment {
+            true => root.first_child().unwrap(),
+            false => root,
+        }
+    }
+
+    /// A `LineIndex` of the source, counting columns in UTF-8 bytes. It's
+    /// built on the first call and then kept for as long as the `Parse` and
+    /// its clones are alive, so consumers don't each need to build their own.
+    pub fn line_index(&self) -> &LineIndex {
+        self.line_index.get_or_init(|| LineIndex::new(&self.source()))
+    }
+
+    /// The same parse without its errors. Clones of a `Parse` share its tree,
+    /// but each has its own copy of the errors, so this saves memory for
+    /// parses that are kept around after their errors were reported.
+    /// Afterwards, `errors` is empty and `ok` succeeds even if the tree
+    /// contains error nodes.
+    pub fn drop_errors(self) -> Parse<T> {
+        Parse { errors: Vec::new(), ..self }
+    }
+
+    /// Whether there were any errors, without looking at them
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// The ranges of all errors in the order of `errors`. Errors without a
+    /// range, like an unexpected end of file, get an empty range at the end.
+    pub fn error_ranges(&self) -> Vec<TextRange> {
+        let end = TextRange::empty(self.green.text_len());
+        self.errors.iter().map(|error| error.range().unwrap_or(end)).collect()
+    }
+
+    /// An indented dump of the tree, one node or token per line with its kind
+    /// and range and the text of tokens, followed by one line per error. This
+    /// is meant for debugging and snapshot tests.
+    pub fn debug_tree(&self) -> String {
+        let mut out = format!("{:#?}", self.syntax());
+        for error in &self.errors {
+            writeln!(out, "error: {}", error).unwrap();
+        }
+        out
+    }
 }
 
 impl<T: AstNode> Parse<T> {
     pub fn tree(&self) -> T {
-        T::cast(self.syntax()).unwrap()
+        T::cast(self.tree_node()).unwrap()
     }
 
     /// Return all errors in the tree, if any
     pub fn errors(&self) -> &[ParseError] {
-        &*self.errors
+        &self.errors
     }
 
     /// Either return the first error in the tree, or if there are none return self
     pub fn ok(self) -> Result<T, ParseError> {
-        if let Some(err) = self.errors().first() {
-            return Err(err.clone());
+        self.ok_all().map_err(|mut errors| errors.swap_remove(0))
+    }
+
+    /// Either return all errors in the tree, or if there are none return self
+    pub fn ok_all(self) -> Result<T, Vec<ParseError>> {
+        if !self.errors.is_empty() {
+            return Err(self.errors);
         }
         Ok(self.tree())
     }
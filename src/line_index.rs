@@ -0,0 +1,257 @@
+//! Conversion between offsets and line/column positions
+
+use rowan::{TextRange, TextSize};
+
+/// The unit columns are counted in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColumnEncoding {
+    /// Bytes of UTF-8
+    #[default]
+    Utf8,
+    /// UTF-16 code units, like the language server protocol uses by default
+    Utf16,
+}
+
+/// A non-ASCII character within a line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct WideChar {
+    /// The UTF-8 column the character starts at
+    start: u32,
+    len_utf8: u32,
+    len_utf16: u32,
+}
+
+/// An index of the lines of a text to convert `TextSize` offsets to zero-based
+/// (line, column) positions and back. Lines end at `\n`, and a `\r` before it
+/// also counts as part of the line ending.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineIndex {
+    /// The offset every line starts at
+    starts: Vec<TextSize>,
+    /// The offset every line ends at, not including its line ending
+    ends: Vec<TextSize>,
+    /// The offset in UTF-16 code units every line starts at
+    starts_utf16: Vec<u32>,
+    wide_chars: Vec<Vec<WideChar>>,
+    encoding: ColumnEncoding,
+}
+
+impl LineIndex {
+    /// Index `text`, counting columns in UTF-8 bytes
+    pub fn new(text: &str) -> Self {
+        Self::with_encoding(text, ColumnEncoding::Utf8)
+    }
+
+    /// Index `text`, counting columns in the given encoding
+    pub fn with_encoding(text: &str, encoding: ColumnEncoding) -> Self {
+        let mut index = Self {
+            starts: Vec::new(),
+            ends: Vec::new(),
+            starts_utf16: Vec::new(),
+            wide_chars: Vec::new(),
+            encoding,
+        };
+        let mut start = 0;
+        let mut start_utf16 = 0;
+        for line in text.split('\n') {
+            let content = line.strip_suffix('\r').unwrap_or(line);
+            index.starts.push(TextSize::from(start as u32));
+            index.starts_utf16.push(start_utf16);
+            index.ends.push(TextSize::from((start + content.len()) as u32));
+            index.wide_chars.push(
+                content
+                    .char_indices()
+                    .filter(|(_, c)| !c.is_ascii())
+                    .map(|(i, c)| WideChar {
+                        start: i as u32,
+                        len_utf8: c.len_utf8() as u32,
+                        len_utf16: c.len_utf16() as u32,
+                    })
+                    .collect(),
+            );
+            start += line.len() + 1;
+            start_utf16 += line.encode_utf16().count() as u32 + 1;
+        }
+        index
+    }
+
+    /// The line and column of `offset`. An offset within a line ending counts
+    /// as the end of its line.
+    ///
+    /// # Panics
+    ///
+    /// If `offset` is past the end of the text.
+    pub fn line_col(&self, offset: TextSize) -> (u32, u32) {
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        assert!(line + 1 < self.starts.len() || offset <= self.ends[line], "offset out of bounds");
+
+        let col = u32::from(offset.min(self.ends[line]) - self.starts[line]);
+        let col = match self.encoding {
+            ColumnEncoding::Utf8 => col,
+            ColumnEncoding::Utf16 => self.col_to_utf16(line, col),
+        };
+        (line as u32, col)
+    }
+
+    fn col_to_utf16(&self, line: usize, col: u32) -> u32 {
+        col - self.wide_chars[line]
+            .iter()
+            .take_while(|c| c.start + c.len_utf8 <= col)
+            .map(|c| c.len_utf8 - c.len_utf16)
+            .sum::<u32>()
+    }
+
+    /// Convert a column of `line` in UTF-16 code units to UTF-8 bytes, or
+    /// `None` if it's within a character
+    fn col_to_utf8(&self, line: usize, col: u32) -> Option<u32> {
+        let wide_chars = &self.wide_chars[line];
+        let mut col = col;
+        for c in wide_chars {
+            if c.start < col {
+                col += c.len_utf8 - c.len_utf16;
+            }
+        }
+        if wide_chars.iter().any(|c| c.start < col && col < c.start + c.len_utf8) {
+            return None;
+        }
+        Some(col)
+    }
+
+    /// The offset of the given line and column, or `None` if the line doesn't
+    /// exist or the column is past its end or within a character
+    pub fn offset(&self, line: u32, col: u32) -> Option<TextSize> {
+        let line = line as usize;
+        let wide_chars = self.wide_chars.get(line)?;
+
+        let col = match self.encoding {
+            ColumnEncoding::Utf8 => {
+                if wide_chars.iter().any(|c| c.start < col && col < c.start + c.len_utf8) {
+                    return None;
+                }
+                col
+            }
+            ColumnEncoding::Utf16 => self.col_to_utf8(line, col)?,
+        };
+
+        let offset = self.starts[line] + TextSize::from(col);
+        (offset <= self.ends[line]).then_some(offset)
+    }
+
+    /// Convert `offset`, which must be at a character boundary, to an offset
+    /// in UTF-16 code units from the start of the text, regardless of the
+    /// column encoding
+    ///
+    /// # Panics
+    ///
+    /// If `offset` is past the end of the text.
+    pub fn to_utf16(&self, offset: TextSize) -> u32 {
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        assert!(line + 1 < self.starts.len() || offset <= self.ends[line], "offset out of bounds");
+        let col = u32::from(offset - self.starts[line]);
+        self.starts_utf16[line] + self.col_to_utf16(line, col)
+    }
+
+    /// Convert an offset in UTF-16 code units from the start of the text to
+    /// `TextSize`, or `None` if it's past the end of the text or in the middle
+    /// of a surrogate pair
+    pub fn from_utf16(&self, offset: u32) -> Option<TextSize> {
+        let line = self.starts_utf16.partition_point(|&start| start <= offset) - 1;
+        let offset = self.starts[line]
+            + TextSize::from(self.col_to_utf8(line, offset - self.starts_utf16[line])?);
+        (line + 1 < self.starts.len() || offset <= self.ends[line]).then_some(offset)
+    }
+}
+
+/// Convert `range` of `source` to a range of offsets in UTF-16 code units, as
+/// used by the language server protocol. This indexes the whole source, so
+/// use `LineIndex::to_utf16` to convert many ranges.
+pub fn utf8_to_utf16(source: &str, range: TextRange) -> (u32, u32) {
+    let index = LineIndex::new(source);
+    (index.to_utf16(range.start()), index.to_utf16(range.end()))
+}
+
+/// Convert a range of offsets in UTF-16 code units back to a `TextRange` of
+/// `source`, or `None` if either end is out of bounds or within a character.
+/// See `utf8_to_utf16`.
+pub fn utf16_to_utf8(source: &str, (start, end): (u32, u32)) -> Option<TextRange> {
+    let index = LineIndex::new(source);
+    let (start, end) = (index.from_utf16(start)?, index.from_utf16(end)?);
+    (start <= end).then(|| TextRange::new(start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "a = \"é\";\r\nb = \"😀x\";\n\nc";
+
+    fn offset(s: &str) -> TextSize {
+        TextSize::of(&TEXT[..TEXT.find(s).unwrap()])
+    }
+
+    #[test]
+    fn utf8() {
+        let index = LineIndex::new(TEXT);
+        assert_eq!(index.line_col(0.into()), (0, 0));
+        assert_eq!(index.line_col(offset("\";\r")), (0, 7));
+        assert_eq!(index.line_col(offset("\r")), (0, 9));
+        assert_eq!(index.line_col(offset("\n")), (0, 9));
+        assert_eq!(index.line_col(offset("b")), (1, 0));
+        assert_eq!(index.line_col(offset("x")), (1, 9));
+        assert_eq!(index.line_col(offset("\nc")), (2, 0));
+        assert_eq!(index.line_col(TextSize::of(TEXT)), (3, 1));
+
+        assert_eq!(index.offset(0, 7), Some(offset("\";\r")));
+        assert_eq!(index.offset(0, 6), None);
+        assert_eq!(index.offset(0, 9), Some(offset("\r")));
+        assert_eq!(index.offset(0, 10), None);
+        assert_eq!(index.offset(1, 9), Some(offset("x")));
+        assert_eq!(index.offset(3, 1), Some(TextSize::of(TEXT)));
+        assert_eq!(index.offset(4, 0), None);
+    }
+
+    #[test]
+    fn utf16() {
+        let index = LineIndex::with_encoding(TEXT, ColumnEncoding::Utf16);
+        assert_eq!(index.line_col(offset("\";\r")), (0, 6));
+        assert_eq!(index.line_col(offset("\r")), (0, 8));
+        assert_eq!(index.line_col(offset("x")), (1, 7));
+        assert_eq!(index.line_col(TextSize::of(TEXT)), (3, 1));
+
+        assert_eq!(index.offset(0, 6), Some(offset("\";\r")));
+        assert_eq!(index.offset(1, 7), Some(offset("x")));
+        assert_eq!(index.offset(1, 6), None);
+        assert_eq!(index.offset(1, 10), Some(offset("\n\n")));
+        assert_eq!(index.offset(1, 11), None);
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        let text = "a 😀 b\n😀";
+        let index = LineIndex::new(text);
+        let b = TextSize::of("a 😀 ");
+        assert_eq!(index.to_utf16(b), 5);
+        assert_eq!(index.to_utf16(TextSize::of(text)), 9);
+        assert_eq!(index.from_utf16(5), Some(b));
+        assert_eq!(index.from_utf16(3), None);
+        assert_eq!(index.from_utf16(9), Some(TextSize::of(text)));
+        assert_eq!(index.from_utf16(10), None);
+
+        let emoji = TextRange::at(2.into(), TextSize::of("😀"));
+        assert_eq!(utf8_to_utf16(text, emoji), (2, 4));
+        assert_eq!(utf16_to_utf8(text, (2, 4)), Some(emoji));
+        assert_eq!(utf16_to_utf8(text, (2, 3)), None);
+        assert_eq!(utf16_to_utf8(text, (7, 9)), Some(TextRange::new(9.into(), TextSize::of(text))));
+    }
+
+    #[test]
+    fn end_of_file() {
+        let index = LineIndex::new("a\n");
+        assert_eq!(index.line_col(2.into()), (1, 0));
+        assert_eq!(index.offset(1, 0), Some(2.into()));
+
+        let index = LineIndex::new("");
+        assert_eq!(index.line_col(0.into()), (0, 0));
+        assert_eq!(index.offset(0, 0), Some(0.into()));
+    }
+}
@@ -0,0 +1,257 @@
+//! Checks for code that parses fine but is rejected or discouraged by Nix
+
+use rowan::ast::AstNode;
+
+use crate::{
+    ast::{self, AstToken, HasEntry},
+    SyntaxKind::{NODE_BIN_OP, NODE_UNARY_OP, TOKEN_URI},
+    SyntaxNode, TextRange,
+};
+
+#[derive(Default)]
+struct Duplicates(Vec<(String, Vec<TextRange>)>);
+
+impl Duplicates {
+    fn report(&mut self, path: String, first: &[TextRange], range: TextRange) {
+        match self.0.iter_mut().find(|(other, _)| *other == path) {
+            Some((_, ranges)) => ranges.push(range),
+            None => self.0.push((path, first.iter().copied().chain([range]).collect())),
+        }
+    }
+}
+
+/// The attributes that `attr_set` defines more than once, which is an error
+/// when evaluating it. Every duplicated attrpath is returned once, in the
+/// order of its first duplicate, with the ranges of the attribute at every
+/// place it's defined.
+///
+/// Bindings are merged as by `ast::merged_entries`, which does the same as
+/// Nix: attrpaths sharing a prefix like `a.b = 1; a.c = 2;` and sets given
+/// literally like `a = { b = 1; }; a.c = 2;` are merged and not duplicates,
+/// but two literals like `a = { b.c = 1; }; a = { b.d = 2; };` only one level
+/// deep, so `a.b` is a duplicate there. Attributes whose names aren't known
+/// statically are skipped.
+pub fn duplicate_keys(attr_set: &ast::AttrSet) -> Vec<(String, Vec<TextRange>)> {
+    let mut duplicates = Duplicates::default();
+    ast::merge_bindings(attr_set, &mut |path, first, range| {
+        duplicates.report(path.to_string(), first, range)
+    });
+    duplicates.0
+}
+
+/// The ranges of all unquoted URIs like `https://nixos.org` in `root`. They
+/// are deprecated, and Nix rejects them with the `no-url-literals` feature.
+pub fn deprecated_uris(root: &SyntaxNode) -> Vec<TextRange> {
+    root.descendants_with_tokens()
+        .filter(|element| element.kind() == TOKEN_URI)
+        .map(|element| element.text_range())
+        .collect()
+}
+
+/// The ranges of the bindings in a `rec` set that obviously recurse
+/// infinitely: those with just their own name as their value, like `a = a;`,
+/// and pairs of bindings with each other's names as their values, like
+/// `a = b; b = a;`. Anything beyond that would need evaluation instead. Sets
+/// that aren't `rec` can't refer to their own bindings, so they are skipped.
+pub fn trivial_rec_cycles(set: &ast::AttrSet) -> Vec<TextRange> {
+    if !set.is_rec() {
+        return Vec::new();
+    }
+    // The name of every binding of a single attribute with the identifier in
+    // its value, if the value is a single identifier
+    let bindings: Vec<_> = set
+        .attrpath_values()
+        .filter_map(|attrpath_value| {
+            let mut attrs = attrpath_value.attrpath()?.attrs();
+            let name = attrs.next()?.static_key()?;
+            if attrs.next().is_some() {
+                return None;
+            }
+            let target = match attrpath_value.value()? {
+                ast::Expr::Ident(ident) => Some(ident.ident_token()?.text().to_string()),
+                _ => None,
+            };
+            Some((name, target, attrpath_value.syntax().text_range()))
+        })
+        .collect();
+    let refers_to = |name: &str, target: &str| {
+        bindings.iter().any(|(other, other_target, _)| {
+            other == name && other_target.as_deref() == Some(target)
+        })
+    };
+    bindings
+        .iter()
+        .filter(|(name, target, _)| {
+            target.as_ref().is_some_and(|target| target == name || refers_to(target, name))
+        })
+        .map(|(_, _, range)| *range)
+        .collect()
+}
+
+/// The ranges of the expressions in `root` whose precedence is easily
+/// misread, since it differs from what their layout or other languages
+/// suggest:
+///
+/// - Lambdas that are operands of an operator without parentheses, like the
+///   one of `1 + x: x + 2`. Their body extends as far to the right as
+///   possible, so the `+ 2` is part of it.
+/// - Negations with `!` of a binary operation, like `!a + b`, which is
+///   `!(a + b)`, and negations compared with `==` or `!=`, like `!a == b`,
+///   which is `(!a) == b`.
+/// - `with` expressions whose body is a binary operation, like
+///   `with x; y + z`, where `z` is in the scope of `x` too.
+///
+/// Parenthesizing the expression that's meant silences these.
+pub fn precedence_pitfalls(root: &SyntaxNode) -> Vec<TextRange> {
+    let is_bin_op = |expr: Option<ast::Expr>| matches!(expr, Some(ast::Expr::BinOp(_)));
+    let is_invert = |expr: Option<ast::Expr>| match expr {
+        Some(ast::Expr::UnaryOp(op)) => op.operator() == Some(ast::UnaryOpKind::Invert),
+        _ => false,
+    };
+    root.descendants()
+        .filter(|node| match ast::Expr::cast(node.clone()) {
+            Some(ast::Expr::Lambda(_)) => node
+                .parent()
+                .is_some_and(|parent| matches!(parent.kind(), NODE_BIN_OP | NODE_UNARY_OP)),
+            Some(ast::Expr::UnaryOp(op)) => {
+                op.operator() == Some(ast::UnaryOpKind::Invert) && is_bin_op(op.expr())
+            }
+            Some(ast::Expr::BinOp(op)) => {
+                matches!(op.operator(), Some(ast::BinOpKind::Equal | ast::BinOpKind::NotEqual))
+                    && is_invert(op.lhs())
+            }
+            Some(ast::Expr::With(with)) => is_bin_op(with.body()),
+            _ => false,
+        })
+        .map(|node| node.text_range())
+        .collect()
+}
+
+/// The ranges of the indented strings in `root` that are indented with both
+/// tabs and spaces, in the same line or in different ones. Nix strips the
+/// same number of characters from every line when dedenting them, whatever
+/// they are, so the result differs from what it looks like in an editor.
+/// Lines with only whitespace don't count, as they don't affect dedenting.
+pub fn indented_string_indent_issues(root: &SyntaxNode) -> Vec<TextRange> {
+    root.descendants()
+        .filter_map(ast::Str::cast)
+        .filter(|str| {
+            let multiline = str.syntax().first_token().is_some_and(|t| t.text() == "''");
+            let (mut tabs, mut spaces) = (false, false);
+            for part in str.parts() {
+                let ast::InterpolPart::Literal(content) = part else { continue };
+                // Every part starts after the `''` or an interpolation, so
+                // only the text after a newline is at the start of a line
+                for line in content.syntax().text().split('\n').skip(1) {
+                    let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+                    if indent < line.len() {
+                        tabs |= line[..indent].contains('\t');
+                        spaces |= line[..indent].contains(' ');
+                    }
+                }
+            }
+            multiline && tabs && spaces
+        })
+        .map(|str| str.syntax().text_range())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::parse_as, Root};
+
+    /// The text of `s` at each of `ranges`
+    fn texts(s: &str, ranges: Vec<TextRange>) -> Vec<&str> {
+        ranges.into_iter().map(|range| &s[range]).collect()
+    }
+
+    fn duplicates(s: &str) -> Vec<(String, Vec<&str>)> {
+        let set = parse_as::<ast::AttrSet>(s);
+        duplicate_keys(&set).into_iter().map(|(path, ranges)| (path, texts(s, ranges))).collect()
+    }
+
+    #[test]
+    fn uris() {
+        let s = r#"[ https://nixos.org "https://example.com" ]"#;
+        let uris = deprecated_uris(&Root::parse(s).syntax());
+        assert_eq!(uris, [TextRange::new(2.into(), 19.into())]);
+    }
+
+    #[test]
+    fn rec_cycles() {
+        fn cycles(s: &str) -> Vec<&str> {
+            texts(s, trivial_rec_cycles(&parse_as(s)))
+        }
+
+        assert_eq!(cycles("rec { a = a; }"), ["a = a;"]);
+        assert_eq!(cycles("rec { a = b; c = 1; b = a; }"), ["a = b;", "b = a;"]);
+        assert!(cycles("rec { a = 1; b = a; }").is_empty());
+        assert!(cycles("rec { a = b; b = c; c = a; }").is_empty());
+        assert!(cycles("rec { a.b = a; c = { c = c; }; }").is_empty());
+        assert!(cycles("{ a = a; }").is_empty());
+    }
+
+    #[test]
+    fn pitfalls() {
+        fn pitfalls(s: &str) -> Vec<&str> {
+            texts(s, precedence_pitfalls(&Root::parse(s).syntax()))
+        }
+
+        assert_eq!(pitfalls("1 + x: x + 2"), ["x: x + 2"]);
+        assert_eq!(pitfalls("a == b: c: d"), ["b: c: d"]);
+        assert_eq!(pitfalls("-x: x"), ["x: x"]);
+        assert_eq!(pitfalls("f (a ++ x: [ x ])"), ["x: [ x ]"]);
+        assert!(pitfalls("1 + (x: x) + 2").is_empty());
+        assert!(pitfalls("[ (x: x) ] ++ map (x: x) [ ]").is_empty());
+
+        assert_eq!(pitfalls("!a + b"), ["!a + b"]);
+        assert_eq!(pitfalls("!a == b"), ["!a == b"]);
+        assert_eq!(pitfalls("c && !a != b"), ["!a != b"]);
+        assert!(pitfalls("!(a + b)").is_empty());
+        assert!(pitfalls("(!a) == b").is_empty());
+        assert!(pitfalls("!a && b").is_empty());
+        assert!(pitfalls("!a ? b || !a.c").is_empty());
+        assert!(pitfalls("a == !b").is_empty());
+
+        assert_eq!(pitfalls("with x; y + z"), ["with x; y + z"]);
+        assert_eq!(pitfalls("f (with x; [ y ] ++ z)"), ["with x; [ y ] ++ z"]);
+        assert!(pitfalls("with x; (y + z)").is_empty());
+        assert!(pitfalls("(with x; y) + z").is_empty());
+        assert!(pitfalls("with x; f y z").is_empty());
+        assert!(pitfalls("{ a = if b then c else d; }").is_empty());
+    }
+
+    #[test]
+    fn indent_issues() {
+        let s = "[\n  ''\n    a\n\t\tb\n  ''\n  ''\n    a\n\n  \t\n    ${b}\n  ''\n  \"\n \tc\"\n  ''\n \ta''\n]";
+        let issues = texts(s, indented_string_indent_issues(&Root::parse(s).syntax()));
+        assert_eq!(issues, ["''\n    a\n\t\tb\n  ''", "''\n \ta''"]);
+    }
+
+    #[test]
+    fn merged() {
+        assert!(duplicates("{ a.b = 1; a.c = 2; }").is_empty());
+        assert!(duplicates("{ a = { b = 1; }; a.c = 2; a = { d = 3; }; }").is_empty());
+        assert!(duplicates("{ a = 1; ${a} = 2; b.${a} = 3; b.${a} = 4; }").is_empty());
+    }
+
+    #[test]
+    fn duplicated() {
+        assert_eq!(duplicates("{ a.b = 1; a.b = 2; }"), [("a.b".to_string(), vec!["b", "b"])]);
+        assert_eq!(
+            duplicates("{ a = 1; inherit a; \"a\" = 3; b = { c = 1; }; b.c.d = 2; }"),
+            [("a".to_string(), vec!["a", "a", "\"a\""]), ("b.c".to_string(), vec!["c", "c"])]
+        );
+        assert_eq!(duplicates("{ a = rec { }; a.b = 1; }"), [("a".to_string(), vec!["a", "a"])]);
+        // Literals bound to the same name are merged only one level deep
+        assert_eq!(
+            duplicates("{ a.b.c = 1; a = { b.d = 2; }; }"),
+            [("a.b".to_string(), vec!["b", "b"])]
+        );
+        assert_eq!(
+            duplicates("{ a = { b.c = 1; }; a = { b.d = 2; }; }"),
+            [("a.b".to_string(), vec!["b", "b"])]
+        );
+    }
+}
@@ -2,98 +2,328 @@
 
 use std::{collections::VecDeque, fmt};
 
-use rowan::{Checkpoint, GreenNode, GreenNodeBuilder, Language, TextRange, TextSize};
+use rowan::{Checkpoint, GreenNode, GreenNodeBuilder, Language, NodeCache, TextRange, TextSize};
 
 use crate::{
-    tokenizer::Token,
+    ast::{BinOpKind, UnaryOpKind},
+    tokenizer::{Token, Tokenizer},
     NixLanguage,
     SyntaxKind::{self, *},
-    TokenSet,
+    SyntaxNode, TokenSet,
 };
 
 /// An error that occurred during parsing
 #[derive(Clone, Debug, PartialEq)]
 #[non_exhaustive]
 pub enum ParseError {
-    /// Unexpected is used when the cause cannot be specified further
-    Unexpected(TextRange),
+    /// Unexpected is used when a token is found where any of `expected` were
+    Unexpected { range: TextRange, found: SyntaxKind, expected: Vec<SyntaxKind> },
     /// UnexpectedExtra is used when there are additional tokens to the root in the tree
-    UnexpectedExtra(TextRange),
-    /// UnexpectedWanted is used when specific tokens are expected, but different one is found
-    UnexpectedWanted(SyntaxKind, TextRange, Box<[SyntaxKind]>),
+    UnexpectedExtra { range: TextRange },
     /// UnexpectedDoubleBind is used when a pattern is bound twice
-    UnexpectedDoubleBind(TextRange),
-    /// UnexpectedEOF is used when the end of file is reached, while tokens are still expected
-    UnexpectedEOF,
-    /// UnexpectedEOFWanted is used when specific tokens are expected, but the end of file is reached
-    UnexpectedEOFWanted(Box<[SyntaxKind]>),
+    UnexpectedDoubleBind { range: TextRange },
+    /// UnexpectedEOF is used when the end of file is reached, while tokens are still expected.
+    /// `expected` may be empty if it's not known what tokens would've been valid.
+    UnexpectedEOF { expected: Vec<SyntaxKind> },
     /// DuplicatedArgs is used when formal arguments are duplicated, e.g. `{ a, a }`
-    DuplicatedArgs(TextRange, String),
-    /// RecursionLimitExceeded is used when we're unable to parse further due to likely being close to
-    /// a stack overflow.
-    RecursionLimitExceeded,
+    DuplicatedArgs { range: TextRange, name: String },
+    /// DepthLimitExceeded is used when expressions are nested deeper than the limit passed to
+    /// `parse_with_limit`. Everything after that is put in an error node instead of overflowing
+    /// the stack.
+    DepthLimitExceeded,
+    /// UnterminatedString is used when a string isn't closed. Double-quoted strings end at the
+    /// first newline after that in this case, indented strings at the end of file.
+    UnterminatedString { range: TextRange },
+    /// UnclosedDelimiter is used when a `(`, `[` or `{` isn't closed before the end of file or the
+    /// closing delimiter of an enclosing one. `open` is the range of the opening delimiter.
+    /// Parsing continues as if it was closed.
+    UnclosedDelimiter { open: TextRange, expected_close: SyntaxKind },
+    /// LegacyLet is used for the deprecated `let { ... }` syntax if
+    /// `ParseConfig::allow_legacy_let` is disabled. It's still parsed as `NODE_LEGACY_LET`.
+    LegacyLet { range: TextRange },
+    /// UnexpectedChar is used for every token the tokenizer couldn't make sense of, such as a
+    /// stray `$`, except unterminated strings and comments. `ch` is its first character.
+    UnexpectedChar { range: TextRange, ch: char },
+    /// UnterminatedComment is used for a `/*` comment that isn't closed before the end of file.
+    /// `range` is that of the whole comment.
+    UnterminatedComment { range: TextRange },
+    /// ExpectedSemicolon is used when a binding ends in a `,` instead of a `;`, as in the JSON-like
+    /// `{ a = 1, b = 2; }`. The comma is put in an error node and parsing continues as if it was
+    /// a `;`.
+    ExpectedSemicolon { found_comma: TextRange },
+    /// ExpectedIn is used when the bindings of a `let` are followed by something that can't be a
+    /// binding, as in `let a = 1; a`. `range` is that of the first token after the bindings,
+    /// which is parsed as the body of the `let` as if there was an `in` before.
+    ExpectedIn { range: TextRange },
+    /// UnclosedInterpolation is used like `UnclosedDelimiter` for a `${` that isn't closed.
+    /// Within a string, the interpolation ends at the quote that most likely ends the string, as
+    /// in `"${a" + 1`, or at the first newline of a double-quoted string, which ends the string
+    /// too.
+    UnclosedInterpolation { open: TextRange },
+    /// EmptyInterpolation is used for an interpolation without an expression, like `${}`.
+    /// `range` is that of the whole interpolation, whose expression is an empty error node.
+    EmptyInterpolation { range: TextRange },
+}
+
+impl ParseError {
+    /// The range of the source this error is about, if it's not at the end of file
+    pub fn range(&self) -> Option<TextRange> {
+        match self {
+            ParseError::Unexpected { range, .. }
+            | ParseError::UnexpectedExtra { range }
+            | ParseError::UnexpectedDoubleBind { range }
+            | ParseError::DuplicatedArgs { range, .. }
+            | ParseError::UnclosedDelimiter { open: range, .. }
+            | ParseError::UnterminatedString { range }
+            | ParseError::LegacyLet { range }
+            | ParseError::UnexpectedChar { range, .. }
+            | ParseError::UnterminatedComment { range }
+            | ParseError::ExpectedSemicolon { found_comma: range }
+            | ParseError::ExpectedIn { range }
+            | ParseError::UnclosedInterpolation { open: range }
+            | ParseError::EmptyInterpolation { range } => Some(*range),
+            ParseError::UnexpectedEOF { .. } | ParseError::DepthLimitExceeded => None,
+        }
+    }
+
+    /// The tokens that would've been valid where this error occurred, if known
+    pub fn expected(&self) -> &[SyntaxKind] {
+        match self {
+            ParseError::Unexpected { expected, .. } | ParseError::UnexpectedEOF { expected } => {
+                expected
+            }
+            ParseError::UnclosedDelimiter { expected_close, .. } => {
+                std::slice::from_ref(expected_close)
+            }
+            ParseError::ExpectedSemicolon { .. } => &[T![;]],
+            ParseError::ExpectedIn { .. } => &[T![in]],
+            ParseError::UnclosedInterpolation { .. } => &[TOKEN_INTERPOL_END],
+            _ => &[],
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ParseError::Unexpected(range) => {
+            ParseError::Unexpected { range, found, expected } => write!(
+                f,
+                "unexpected {:?} at {}..{}, wanted any of {:?}",
+                found,
+                usize::from(range.start()),
+                usize::from(range.end()),
+                expected
+            ),
+            ParseError::UnexpectedExtra { range } => {
                 write!(
                     f,
-                    "error node at {}..{}",
+                    "unexpected token at {}..{}",
                     usize::from(range.start()),
                     usize::from(range.end())
                 )
             }
-            ParseError::UnexpectedExtra(range) => {
+            ParseError::UnexpectedDoubleBind { range } => {
                 write!(
                     f,
-                    "unexpected token at {}..{}",
+                    "unexpected double bind at {}..{}",
                     usize::from(range.start()),
                     usize::from(range.end())
                 )
             }
-            ParseError::UnexpectedWanted(got, range, kinds) => write!(
-                f,
-                "unexpected {:?} at {}..{}, wanted any of {:?}",
-                got,
-                usize::from(range.start()),
-                usize::from(range.end()),
-                kinds
-            ),
-            ParseError::UnexpectedDoubleBind(range) => {
+            ParseError::UnexpectedEOF { expected } if expected.is_empty() => {
+                write!(f, "unexpected end of file")
+            }
+            ParseError::UnexpectedEOF { expected } => {
+                write!(f, "unexpected end of file, wanted any of {:?}", expected)
+            }
+            ParseError::DuplicatedArgs { range, name } => {
                 write!(
                     f,
-                    "unexpected double bind at {}..{}",
+                    "argument `{}` is duplicated in {}..{}",
+                    name,
                     usize::from(range.start()),
                     usize::from(range.end())
                 )
             }
-            ParseError::UnexpectedEOF => write!(f, "unexpected end of file"),
-            ParseError::UnexpectedEOFWanted(kinds) => {
-                write!(f, "unexpected end of file, wanted any of {:?}", kinds)
+            ParseError::DepthLimitExceeded => write!(f, "depth limit exceeded"),
+            ParseError::UnterminatedString { range } => {
+                write!(
+                    f,
+                    "unterminated string at {}..{}",
+                    usize::from(range.start()),
+                    usize::from(range.end())
+                )
             }
-            ParseError::DuplicatedArgs(range, ident) => {
+            ParseError::UnclosedDelimiter { open, expected_close } => {
                 write!(
                     f,
-                    "argument `{}` is duplicated in {}..{}",
-                    ident,
+                    "unclosed delimiter at {}..{}, wanted {:?}",
+                    usize::from(open.start()),
+                    usize::from(open.end()),
+                    expected_close
+                )
+            }
+            ParseError::LegacyLet { range } => {
+                write!(
+                    f,
+                    "deprecated `let {{ ... }}` syntax at {}..{}, use `let ... in` instead",
+                    usize::from(range.start()),
+                    usize::from(range.end())
+                )
+            }
+            ParseError::UnexpectedChar { range, ch } => {
+                write!(
+                    f,
+                    "unexpected character {:?} at {}..{}",
+                    ch,
+                    usize::from(range.start()),
+                    usize::from(range.end())
+                )
+            }
+            ParseError::UnterminatedComment { range } => {
+                write!(
+                    f,
+                    "unterminated comment at {}..{}",
+                    usize::from(range.start()),
+                    usize::from(range.end())
+                )
+            }
+            ParseError::ExpectedSemicolon { found_comma } => {
+                write!(
+                    f,
+                    "unexpected `,` at {}..{}, bindings are separated by `;` instead",
+                    usize::from(found_comma.start()),
+                    usize::from(found_comma.end())
+                )
+            }
+            ParseError::ExpectedIn { range } => {
+                write!(
+                    f,
+                    "expected `in` before the body of `let` at {}..{}",
+                    usize::from(range.start()),
+                    usize::from(range.end())
+                )
+            }
+            ParseError::UnclosedInterpolation { open } => {
+                write!(
+                    f,
+                    "unclosed interpolation at {}..{}",
+                    usize::from(open.start()),
+                    usize::from(open.end())
+                )
+            }
+            ParseError::EmptyInterpolation { range } => {
+                write!(
+                    f,
+                    "empty interpolation at {}..{}",
                     usize::from(range.start()),
                     usize::from(range.end())
                 )
             }
-            ParseError::RecursionLimitExceeded => write!(f, "recursion limit exceeded"),
         }
     }
 }
 
 impl std::error::Error for ParseError {}
 
-struct Parser<'a, I>
+/// The associativity of a binary operator
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Assoc {
+    /// `a op b op c` is `(a op b) op c`
+    Left,
+    /// `a op b op c` is `a op (b op c)`
+    Right,
+    /// `a op b op c` is a syntax error
+    None,
+}
+
+/// Get the left and right binding power of an infix operator, or `None` if
+/// `kind` isn't one. Function application, which has no token, is looked up
+/// with `NODE_APPLY`.
+///
+/// An operator binds tighter the higher its binding power is. For left- and
+/// non-associative operators the right power is one more than the left one,
+/// for right-associative operators it is one less:
+///
+/// | operator                  | binding power | associativity |
+/// |---------------------------|---------------|---------------|
+/// | `<\|`                     | (2, 1)        | right         |
+/// | `\|>`                     | (3, 4)        | left          |
+/// | `->`                      | (6, 5)        | right         |
+/// | `\|\|`                    | (7, 8)        | left          |
+/// | `&&`                      | (9, 10)       | left          |
+/// | `==`, `!=`                | (11, 12)      | none          |
+/// | `<`, `<=`, `>`, `>=`      | (13, 14)      | none          |
+/// | `//`                      | (16, 15)      | right         |
+/// | `!` (prefix)              | (0, 17)       |               |
+/// | `+`, `-`                  | (19, 20)      | left          |
+/// | `*`, `/`                  | (21, 22)      | left          |
+/// | `++`                      | (24, 23)      | right         |
+/// | `?`                       | (25, 26)      | left          |
+/// | `-` (prefix)              | (0, 27)       |               |
+/// | application               | (29, 30)      | left          |
+/// | `.`                       | (31, 32)      | left          |
+///
+/// `TOKEN_SUB` is always treated as the infix `-`, see
+/// `prefix_binding_power` for negation.
+pub fn binding_power(kind: SyntaxKind) -> Option<(u8, u8)> {
+    let power = match kind {
+        T!["<|"] => (2, 1),
+        T!["|>"] => (3, 4),
+        T![->] => (6, 5),
+        T![||] => (7, 8),
+        T![&&] => (9, 10),
+        T![==] | T![!=] => (11, 12),
+        T![<] | T![<=] | T![>] | T![>=] => (13, 14),
+        T!["//"] => (16, 15),
+        T![+] | T![-] => (19, 20),
+        T![*] | T![/] => (21, 22),
+        T![++] => (24, 23),
+        T![?] => (25, 26),
+        NODE_APPLY => (29, 30),
+        T![.] => (31, 32),
+        _ => return None,
+    };
+    Some(power)
+}
+
+/// Get the right binding power of a prefix operator. See `binding_power`.
+pub fn prefix_binding_power(op: UnaryOpKind) -> u8 {
+    match op {
+        UnaryOpKind::Invert => 17,
+        UnaryOpKind::Negate => 27,
+    }
+}
+
+/// Get the associativity of a binary operator
+pub fn operator_assoc(op: BinOpKind) -> Assoc {
+    match op {
+        BinOpKind::Concat | BinOpKind::Update | BinOpKind::Implication | BinOpKind::PipeLeft => {
+            Assoc::Right
+        }
+
+        BinOpKind::Add
+        | BinOpKind::Sub
+        | BinOpKind::Mul
+        | BinOpKind::Div
+        | BinOpKind::And
+        | BinOpKind::Or
+        | BinOpKind::PipeRight => Assoc::Left,
+
+        BinOpKind::Equal
+        | BinOpKind::NotEqual
+        | BinOpKind::Less
+        | BinOpKind::LessOrEq
+        | BinOpKind::More
+        | BinOpKind::MoreOrEq => Assoc::None,
+    }
+}
+
+struct Parser<'a, 'c, I>
 where
     I: Iterator<Item = Token<'a>>,
 {
-    builder: GreenNodeBuilder<'static>,
+    builder: GreenNodeBuilder<'c>,
     errors: Vec<ParseError>,
 
     trivia_buffer: Vec<Token<'a>>,
@@ -103,15 +333,21 @@ where
 
     // Recursion depth, used for avoiding stack overflows. This may be incremented
     // by any method as long as it is decremented when that method returns.
-    depth: u32,
+    depth: usize,
+    config: ParseConfig,
+
+    // The closing delimiters of all currently open ones, innermost last, including the `;` of
+    // bindings. Recovery never consumes these, so that the delimiters they belong to can still be
+    // closed.
+    delimiters: Vec<SyntaxKind>,
 }
-impl<'a, I> Parser<'a, I>
+impl<'a, 'c, I> Parser<'a, 'c, I>
 where
     I: Iterator<Item = Token<'a>>,
 {
-    fn new(iter: I) -> Self {
+    fn new(iter: I, config: ParseConfig, builder: GreenNodeBuilder<'c>) -> Self {
         Self {
-            builder: GreenNodeBuilder::new(),
+            builder,
             errors: Vec::new(),
 
             trivia_buffer: Vec::with_capacity(1),
@@ -120,6 +356,9 @@ where
             consumed: TextSize::from(0),
 
             depth: 0,
+            config,
+
+            delimiters: Vec::new(),
         }
     }
 
@@ -127,9 +366,26 @@ where
         self.consumed
     }
 
+    /// The next token from the tokenizer, with `or` as a plain identifier if
+    /// it isn't a keyword
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        match self.iter.next()? {
+            (TOKEN_OR, s) if !self.config.or_as_keyword => Some((TOKEN_IDENT, s)),
+            token => Some(token),
+        }
+    }
+    /// Whether parsing stopped because of an error, see `ParseConfig::recover`
+    fn stopped(&self) -> bool {
+        !self.config.recover && !self.errors.is_empty()
+    }
     fn peek_raw(&mut self) -> Option<&Token<'a>> {
+        // Pretend the file ends here, so that everything unwinds without
+        // recovering
+        if self.stopped() {
+            return None;
+        }
         if self.buffer.is_empty() {
-            if let Some(token) = self.iter.next() {
+            if let Some(token) = self.next_token() {
                 self.buffer.push_back(token);
             }
         }
@@ -174,14 +430,24 @@ where
                     self.trivia_buffer.push((token, s))
                 } else {
                     self.drain_trivia_buffer();
+                    if token == TOKEN_ERROR {
+                        let start = self.get_text_position();
+                        let range = TextRange::at(start, TextSize::of(s));
+                        self.errors.push(match s.strip_prefix("/*") {
+                            Some(_) => ParseError::UnterminatedComment { range },
+                            None => {
+                                ParseError::UnexpectedChar { range, ch: s.chars().next().unwrap() }
+                            }
+                        });
+                    }
                     self.manual_bump(s, token);
                 }
             }
-            None => self.errors.push(ParseError::UnexpectedEOF),
+            None => self.errors.push(ParseError::UnexpectedEOF { expected: Vec::new() }),
         }
     }
     fn try_next(&mut self) -> Option<Token<'a>> {
-        self.buffer.pop_front().or_else(|| self.iter.next())
+        self.buffer.pop_front().or_else(|| self.next_token())
     }
     fn manual_bump(&mut self, s: &str, token: SyntaxKind) {
         self.consumed += TextSize::of(s);
@@ -196,42 +462,201 @@ where
     fn peek(&mut self) -> Option<SyntaxKind> {
         self.peek_data().map(|&(t, _)| t)
     }
+    /// Peek at the `n`th next non-trivia token, `peek_nth(0)` being the same as `peek()`
+    fn peek_nth(&mut self, n: usize) -> Option<SyntaxKind> {
+        self.peek()?;
+        let mut seen = 0;
+        for i in 0.. {
+            if i == self.buffer.len() {
+                let token = self.next_token()?;
+                self.buffer.push_back(token);
+            }
+            let kind = self.buffer[i].0;
+            if !kind.is_trivia() {
+                if seen == n {
+                    return Some(kind);
+                }
+                seen += 1;
+            }
+        }
+        unreachable!()
+    }
+    /// The range of the next non-trivia token
+    fn peek_range(&mut self) -> Option<TextRange> {
+        let &(_, s) = self.peek_data()?;
+        let len = TextSize::of(s);
+        let start = self.consumed
+            + self.trivia_buffer.iter().map(|(_, s)| TextSize::of(*s)).sum::<TextSize>();
+        Some(TextRange::at(start, len))
+    }
+    /// Whether the next tokens look like the start of a binding, like `a =`.
+    /// This is never valid within an expression, so it's used to recover from
+    /// a missing `;`.
+    fn at_binding_start(&mut self) -> bool {
+        self.peek() == Some(TOKEN_IDENT) && self.peek_nth(1) == Some(T![=])
+    }
+    /// Whether the next tokens could be a binding of a `let`, rather than its
+    /// body after a missing `in`. Something like `a.b` is a binding if it's
+    /// followed by a `=` or `;`, or by an `in` that would end the bindings, at
+    /// the same level of nesting.
+    fn at_let_binding(&mut self) -> bool {
+        match self.peek() {
+            Some(T![inherit]) => return true,
+            Some(TOKEN_IDENT | TOKEN_OR | TOKEN_STRING_START | TOKEN_INTERPOL_START) => (),
+            _ => return false,
+        }
+        let mut depth = 0usize;
+        for i in 0.. {
+            if i == self.buffer.len() {
+                match self.next_token() {
+                    Some(token) => self.buffer.push_back(token),
+                    None => return false,
+                }
+            }
+            match self.buffer[i].0 {
+                T![=] | T![;] | T![in] if depth == 0 => return true,
+                T!['('] | T!['['] | T!['{'] | TOKEN_INTERPOL_START => depth += 1,
+                T![')'] | T![']'] | T!['}'] | TOKEN_INTERPOL_END => match depth.checked_sub(1) {
+                    Some(outer) => depth = outer,
+                    None => return false,
+                },
+                _ => (),
+            }
+        }
+        unreachable!()
+    }
     fn expect_peek_any(&mut self, allowed_slice: &[SyntaxKind]) -> Option<SyntaxKind> {
         let allowed = TokenSet::from_slice(allowed_slice);
 
         let next = match self.peek() {
             None => None,
             Some(kind) if allowed.contains(kind) => Some(kind),
+            Some(kind) if self.at_enclosing_close() => {
+                let range = self.peek_range().unwrap();
+                self.errors.push(ParseError::Unexpected {
+                    range,
+                    found: kind,
+                    expected: allowed_slice.to_vec(),
+                });
+                return None;
+            }
             Some(kind) => {
                 let start = self.start_error_node();
                 loop {
                     self.bump();
-                    if self.peek().map(|kind| allowed.contains(kind)).unwrap_or(true) {
+                    if self.peek().map(|kind| allowed.contains(kind)).unwrap_or(true)
+                        || self.at_enclosing_close()
+                    {
                         break;
                     }
                 }
                 let end = self.finish_error_node();
-                self.errors.push(ParseError::UnexpectedWanted(
-                    kind,
-                    TextRange::new(start, end),
-                    allowed_slice.to_vec().into_boxed_slice(),
-                ));
+                // Error tokens are reported by `bump` already
+                if kind != TOKEN_ERROR {
+                    self.errors.push(ParseError::Unexpected {
+                        range: TextRange::new(start, end),
+                        found: kind,
+                        expected: allowed_slice.to_vec(),
+                    });
+                }
 
-                self.peek()
+                match self.peek() {
+                    Some(kind) if !allowed.contains(kind) => return None,
+                    next => next,
+                }
             }
         };
         if next.is_none() {
-            self.errors
-                .push(ParseError::UnexpectedEOFWanted(allowed_slice.to_vec().into_boxed_slice()));
+            self.errors.push(ParseError::UnexpectedEOF { expected: allowed_slice.to_vec() });
         }
         next
     }
+    /// Whether the next token closes one of the open delimiters
+    fn at_enclosing_close(&mut self) -> bool {
+        self.peek().is_some_and(|kind| self.delimiters.contains(&kind))
+    }
+    /// Bump an opening delimiter that's closed by `close` and return its range
+    fn open_delimiter(&mut self, close: SyntaxKind) -> TextRange {
+        let range = self.peek_range().unwrap();
+        self.bump();
+        self.delimiters.push(close);
+        range
+    }
+    /// Expect the `close` of the delimiter opened at `open`. If another closing delimiter or the
+    /// end of file comes first, report it as unclosed and carry on as if it was closed.
+    fn close_delimiter(&mut self, open: TextRange, close: SyntaxKind) {
+        self.delimiters.pop();
+        let unclosed = match close {
+            TOKEN_INTERPOL_END => ParseError::UnclosedInterpolation { open },
+            _ => ParseError::UnclosedDelimiter { open, expected_close: close },
+        };
+        match self.peek() {
+            Some(kind) if kind == close => self.bump(),
+            None | Some(T![')'] | T![']'] | T!['}'] | TOKEN_INTERPOL_END | TOKEN_STRING_END) => {
+                self.errors.push(unclosed)
+            }
+            // The `;` ending the binding this is in
+            Some(kind) if self.delimiters.contains(&kind) => self.errors.push(unclosed),
+            Some(_) => self.expect(close),
+        }
+    }
     fn expect(&mut self, expected: SyntaxKind) {
         if self.expect_peek_any(&[expected]).is_some() {
             self.bump();
         }
     }
 
+    /// Whether the binding started when there were `errors` errors is broken and at its end, so
+    /// that there's no use in expecting the rest of it. Only one error is reported per binding.
+    fn at_broken_binding_end(&mut self, errors: usize) -> bool {
+        self.errors.len() > errors && (self.peek().is_none() || self.at_enclosing_close())
+    }
+    /// Skip the rest of a broken binding without reporting another error, up to and including
+    /// its `;`. Stops before the closing delimiter of the set or the next binding, if the `;` is
+    /// missing.
+    fn recover_binding(&mut self) {
+        let at_end = |p: &mut Self| {
+            p.peek().is_none_or(|kind| kind == T![;] || p.delimiters.contains(&kind))
+                || p.at_binding_start()
+        };
+        if !at_end(self) {
+            self.start_error_node();
+            while !at_end(self) {
+                self.bump();
+            }
+            self.finish_error_node();
+        }
+        if self.peek() == Some(T![;]) {
+            self.bump();
+        }
+    }
+    /// Expect the `;` ending a binding. If something else comes first, report it and skip to the
+    /// end of the binding, but not past the start of the next one.
+    fn expect_semicolon(&mut self) {
+        match self.peek() {
+            Some(T![;]) => self.bump(),
+            Some(T![,]) => {
+                let found_comma = self.peek_range().unwrap();
+                self.errors.push(ParseError::ExpectedSemicolon { found_comma });
+                self.start_error_node();
+                self.bump();
+                self.finish_error_node();
+            }
+            None => self.errors.push(ParseError::UnexpectedEOF { expected: vec![T![;]] }),
+            Some(kind) => {
+                // Error tokens are reported by `bump` when skipping them
+                if kind != TOKEN_ERROR {
+                    let range = self.peek_range().unwrap();
+                    self.errors.push(ParseError::Unexpected {
+                        range,
+                        found: kind,
+                        expected: vec![T![;]],
+                    });
+                }
+                self.recover_binding();
+            }
+        }
+    }
     fn expect_ident(&mut self) {
         if self.expect_peek_any(&[TOKEN_IDENT]).is_some() {
             self.start_node(NODE_IDENT);
@@ -240,13 +665,28 @@ where
         }
     }
 
+    /// Report an empty interpolation `${}` opened at `open` if that's what's next, putting an empty
+    /// error node in place of its expression
+    fn empty_interpolation(&mut self, open: TextRange) -> bool {
+        if self.peek() != Some(TOKEN_INTERPOL_END) {
+            return false;
+        }
+        let close = self.peek_range().unwrap();
+        self.start_node(NODE_ERROR);
+        self.finish_node();
+        self.errors.push(ParseError::EmptyInterpolation { range: open.cover(close) });
+        true
+    }
+
     fn parse_dynamic(&mut self) {
         self.start_node(NODE_DYNAMIC);
-        self.bump();
-        while self.peek().map(|t| t != TOKEN_INTERPOL_END).unwrap_or(false) {
-            self.parse_expr();
+        let open = self.open_delimiter(TOKEN_INTERPOL_END);
+        if !self.empty_interpolation(open) {
+            while self.peek().is_some() && !self.at_enclosing_close() {
+                self.parse_expr();
+            }
         }
-        self.bump();
+        self.close_delimiter(open, TOKEN_INTERPOL_END);
         self.finish_node();
     }
 
@@ -255,6 +695,17 @@ where
         self.expect(TOKEN_STRING_START);
 
         loop {
+            // The tokenizer emits an error token for the rest of an unterminated string
+            if self.peek() == Some(TOKEN_ERROR) {
+                let start = self.start_error_node();
+                let (kind, s) = self.try_next().unwrap();
+                self.manual_bump(s, kind);
+                let end = self.finish_error_node();
+                self.errors
+                    .push(ParseError::UnterminatedString { range: TextRange::new(start, end) });
+                self.finish_node();
+                return;
+            }
             match self.expect_peek_any(&[
                 TOKEN_STRING_END,
                 TOKEN_STRING_CONTENT,
@@ -263,10 +714,25 @@ where
                 Some(TOKEN_STRING_CONTENT) => self.bump(),
                 Some(TOKEN_INTERPOL_START) => {
                     self.start_node(NODE_INTERPOL);
-                    self.bump();
-                    self.parse_expr();
-                    self.expect(TOKEN_INTERPOL_END);
+                    let open = self.open_delimiter(TOKEN_INTERPOL_END);
+                    if !self.empty_interpolation(open) {
+                        self.parse_expr();
+                    }
+                    self.close_delimiter(open, TOKEN_INTERPOL_END);
                     self.finish_node();
+                    // An unclosed interpolation ended at a newline or the end of
+                    // file ends the string as well, see `parse_text`
+                    let unclosed =
+                        self.errors.last() == Some(&ParseError::UnclosedInterpolation { open });
+                    if unclosed
+                        && !matches!(
+                            self.peek(),
+                            Some(TOKEN_STRING_END | TOKEN_STRING_CONTENT | TOKEN_INTERPOL_START)
+                        )
+                    {
+                        self.finish_node();
+                        return;
+                    }
                 }
                 // handled by expect_peek_any
                 _ => break,
@@ -348,29 +814,36 @@ where
             self.expect_ident();
             let end = self.finish_error_node();
             if bound {
-                self.errors.push(ParseError::UnexpectedDoubleBind(TextRange::new(start, end)));
+                self.errors
+                    .push(ParseError::UnexpectedDoubleBind { range: TextRange::new(start, end) });
             }
         }
     }
+    /// Parse bindings up to `until`, which is left for the caller
     fn parse_set(&mut self, until: SyntaxKind) {
         loop {
             match self.peek() {
                 None => break,
                 token if token == Some(until) => break,
+                Some(_) if self.at_enclosing_close() => break,
+                // The body of a `let` with a missing `in`
+                Some(_) if until == T![in] && !self.at_let_binding() => break,
                 Some(T![inherit]) => {
                     self.start_node(NODE_INHERIT);
                     self.bump();
 
                     if self.peek() == Some(T!['(']) {
                         self.start_node(NODE_INHERIT_FROM);
-                        self.bump();
+                        let open = self.open_delimiter(T![')']);
                         self.parse_expr();
-                        self.expect(T![')']);
+                        self.close_delimiter(open, T![')']);
                         self.finish_node();
                     }
 
+                    self.delimiters.push(T![;]);
                     loop {
                         match self.peek() {
+                            Some(_) if self.at_enclosing_close() => break,
                             Some(t) if t != T![;] => {
                                 self.parse_attr();
                             }
@@ -378,33 +851,51 @@ where
                                 break;
                             }
                             None => {
-                                self.errors.push(ParseError::UnexpectedEOF);
+                                self.errors
+                                    .push(ParseError::UnexpectedEOF { expected: Vec::new() });
                                 break;
                             }
                         }
                     }
+                    self.delimiters.pop();
 
                     self.expect(T![;]);
                     self.finish_node();
                 }
                 Some(_) => {
                     self.start_node(NODE_ATTRPATH_VALUE);
+                    let errors = self.errors.len();
+                    self.delimiters.push(T![;]);
                     self.parse_attrpath();
-                    self.expect(T![=]);
-                    self.parse_expr();
-                    self.expect(T![;]);
+                    if !self.at_broken_binding_end(errors) {
+                        self.expect(T![=]);
+                    }
+                    if !self.at_broken_binding_end(errors) {
+                        self.parse_expr();
+                    }
+                    self.delimiters.pop();
+                    if self.errors.len() > errors {
+                        self.recover_binding();
+                    } else {
+                        self.expect_semicolon();
+                    }
                     self.finish_node();
                 }
             }
         }
-        self.bump(); // the final close, like '}'
+    }
+    /// Parse the `{ ... }` of an attrset, starting at the `{`
+    fn parse_set_braces(&mut self) {
+        let open = self.open_delimiter(T!['}']);
+        self.parse_set(T!['}']);
+        self.close_delimiter(open, T!['}']);
     }
 
     fn parse_simple(&mut self) -> Checkpoint {
         let peek = match self.peek() {
             Some(it) => it,
             None => {
-                self.errors.push(ParseError::UnexpectedEOF);
+                self.errors.push(ParseError::UnexpectedEOF { expected: Vec::new() });
                 // NB: we don't use `self.checkpoint()` here in order to avoid
                 // eating the whitespace. The actual checkpoint doesn't matter
                 // in this case and, ideally, should be returning `None`, but
@@ -417,16 +908,17 @@ where
         match peek {
             T!['('] => {
                 self.start_node(NODE_PAREN);
-                self.bump();
+                let open = self.open_delimiter(T![')']);
                 self.parse_expr();
-                self.bump();
+                self.close_delimiter(open, T![')']);
                 self.finish_node();
             }
             T![rec] => {
                 self.start_node(NODE_ATTR_SET);
                 self.bump();
-                self.expect(T!['{']);
-                self.parse_set(T!['}']);
+                if self.expect_peek_any(&[T!['{']]).is_some() {
+                    self.parse_set_braces();
+                }
                 self.finish_node();
             }
             T!['{'] => {
@@ -435,7 +927,7 @@ where
                 for i in &mut peek {
                     let mut token;
                     *i = loop {
-                        token = self.iter.next();
+                        token = self.next_token();
                         let kind = token.as_ref().map(|&(t, _)| t);
                         if let Some(token) = token {
                             self.buffer.push_back(token);
@@ -469,35 +961,47 @@ where
                     _ => {
                         // This looks like a set
                         self.start_node(NODE_ATTR_SET);
-                        self.bump();
-                        self.parse_set(T!['}']);
+                        self.parse_set_braces();
                         self.finish_node();
                     }
                 }
             }
             T!['['] => {
                 self.start_node(NODE_LIST);
-                self.bump();
-                while self.peek().map(|t| t != T![']']).unwrap_or(false) {
+                let open = self.open_delimiter(T![']']);
+                while self.peek().is_some() && !self.at_enclosing_close() {
+                    if !self.enter_nested() {
+                        break;
+                    }
                     self.parse_simple();
+                    self.leave_nested();
                 }
-                self.bump();
+                self.close_delimiter(open, T![']']);
                 self.finish_node();
             }
             TOKEN_STRING_START => self.parse_string(),
+            // Search paths can't contain interpolations
+            TOKEN_SPATH => {
+                self.start_node(NODE_PATH);
+                self.bump();
+                self.finish_node();
+            }
             TOKEN_PATH => {
                 self.start_node(NODE_PATH);
                 self.bump();
-                let is_complex_path = self.peek().map_or(false, |t| t == TOKEN_INTERPOL_START);
+                let is_complex_path =
+                    self.peek_raw().map(|(t, _)| *t) == Some(TOKEN_INTERPOL_START);
                 if is_complex_path {
                     loop {
                         match self.peek_raw().map(|(t, _)| t) {
                             Some(TOKEN_PATH) => self.bump(),
                             Some(TOKEN_INTERPOL_START) => {
                                 self.start_node(NODE_INTERPOL);
-                                self.bump();
-                                self.parse_expr();
-                                self.expect(TOKEN_INTERPOL_END);
+                                let open = self.open_delimiter(TOKEN_INTERPOL_END);
+                                if !self.empty_interpolation(open) {
+                                    self.parse_expr();
+                                }
+                                self.close_delimiter(open, TOKEN_INTERPOL_END);
                                 self.finish_node();
                             }
                             _ => break,
@@ -541,17 +1045,34 @@ where
                     _ => (),
                 }
             }
-            kind => {
-                let start = self.start_error_node();
+            TOKEN_ERROR => {
+                // Reported by `bump` as an unexpected character
+                self.start_error_node();
                 self.bump();
-                let end = self.finish_error_node();
-                self.errors.push(ParseError::UnexpectedWanted(
-                    kind,
-                    TextRange::new(start, end),
-                    [T!['('], T![rec], T!['{'], T!['['], TOKEN_STRING_START, TOKEN_IDENT]
-                        .to_vec()
-                        .into_boxed_slice(),
-                ));
+                self.finish_error_node();
+            }
+            kind => {
+                let range = if self.at_enclosing_close() {
+                    // Leave it for the delimiter it closes
+                    self.peek_range().unwrap()
+                } else {
+                    let start = self.start_error_node();
+                    self.bump();
+                    let end = self.finish_error_node();
+                    TextRange::new(start, end)
+                };
+                self.errors.push(ParseError::Unexpected {
+                    range,
+                    found: kind,
+                    expected: vec![
+                        T!['('],
+                        T![rec],
+                        T!['{'],
+                        T!['['],
+                        TOKEN_STRING_START,
+                        TOKEN_IDENT,
+                    ],
+                });
             }
         };
 
@@ -586,7 +1107,7 @@ where
     fn parse_fn(&mut self) -> Checkpoint {
         let checkpoint = self.parse_simple();
 
-        while self.peek().map(|t| t.is_fn_arg()).unwrap_or(false) {
+        while self.peek().map(|t| t.is_fn_arg()).unwrap_or(false) && !self.at_binding_start() {
             self.start_node_at(checkpoint, NODE_APPLY);
             self.parse_simple();
             self.finish_node();
@@ -598,7 +1119,10 @@ where
             let checkpoint = self.checkpoint();
             self.start_node(NODE_UNARY_OP);
             self.bump();
-            self.parse_negate();
+            if self.enter_nested() {
+                self.parse_negate();
+                self.leave_nested();
+            }
             self.finish_node();
             checkpoint
         } else {
@@ -634,7 +1158,10 @@ where
         if self.peek().map(|t| ops.contains(t)).unwrap_or(false) {
             self.start_node_at(checkpoint, NODE_BIN_OP);
             self.bump();
-            self.parse_right_assoc(next, ops);
+            if self.enter_nested() {
+                self.parse_right_assoc(next, ops);
+                self.leave_nested();
+            }
             self.finish_node();
         }
         checkpoint
@@ -663,7 +1190,10 @@ where
             let checkpoint = self.checkpoint();
             self.start_node(NODE_UNARY_OP);
             self.bump();
-            self.parse_invert();
+            if self.enter_nested() {
+                self.parse_invert();
+                self.leave_nested();
+            }
             self.finish_node();
             checkpoint
         } else {
@@ -699,11 +1229,12 @@ where
     fn parse_pipe_left(&mut self) -> Checkpoint {
         self.parse_right_assoc(Self::parse_pipe_right, T!["<|"] | ())
     }
-    /// Parse Nix code into an AST
-    pub fn parse_expr(&mut self) -> Checkpoint {
-        // Limit chosen somewhat arbitrarily
-        if self.depth >= 512 {
-            self.errors.push(ParseError::RecursionLimitExceeded);
+    /// Enter another level of nesting. If that exceeds the depth limit, report
+    /// it, put the rest of the input into an error node and return false.
+    /// Otherwise `leave_nested` must be called afterwards.
+    fn enter_nested(&mut self) -> bool {
+        if self.depth >= self.config.max_depth {
+            self.errors.push(ParseError::DepthLimitExceeded);
             // Consume tokens to the end of the file. Erroring without bumping might cause
             // infinite looping elsewhere.
             self.start_error_node();
@@ -711,22 +1242,44 @@ where
                 self.bump()
             }
             self.finish_error_node();
-            return self.checkpoint();
+            return false;
         }
         self.depth += 1;
+        true
+    }
+    fn leave_nested(&mut self) {
+        self.depth -= 1;
+    }
+    /// Parse Nix code into an AST
+    pub fn parse_expr(&mut self) -> Checkpoint {
+        if !self.enter_nested() {
+            return self.checkpoint();
+        }
         let out = match self.peek() {
             Some(T![let]) => {
                 let checkpoint = self.checkpoint();
+                let start = self.get_text_position();
                 self.bump();
 
                 if self.peek() == Some(T!['{']) {
                     self.start_node_at(checkpoint, NODE_LEGACY_LET);
-                    self.bump();
-                    self.parse_set(T!['}']);
+                    self.parse_set_braces();
                     self.finish_node();
+                    if !self.config.allow_legacy_let {
+                        let range = TextRange::new(start, self.get_text_position());
+                        self.errors.push(ParseError::LegacyLet { range });
+                    }
                 } else {
                     self.start_node_at(checkpoint, NODE_LET_IN);
                     self.parse_set(T![in]);
+                    match self.peek() {
+                        Some(T![in]) | None => self.expect(T![in]),
+                        Some(_) if self.at_enclosing_close() => self.expect(T![in]),
+                        Some(_) => {
+                            let range = self.peek_range().unwrap();
+                            self.errors.push(ParseError::ExpectedIn { range });
+                        }
+                    }
                     self.parse_expr();
                     self.finish_node();
                 }
@@ -746,9 +1299,18 @@ where
                 let checkpoint = self.checkpoint();
                 self.start_node(NODE_IF_ELSE);
                 self.bump();
+                // Recovery in a broken part doesn't consume the keywords after it, so that each
+                // part after them still ends up after its keyword
+                self.delimiters.extend([T![else], T![then]]);
                 self.parse_expr();
+                self.delimiters.pop();
+                let errors = self.errors.len();
                 self.expect(T![then]);
-                self.parse_expr();
+                // Only one error for `if a else b`
+                if self.errors.len() == errors || self.peek() != Some(TOKEN_ELSE) {
+                    self.parse_expr();
+                }
+                self.delimiters.pop();
                 self.expect(TOKEN_ELSE);
                 self.parse_expr();
                 self.finish_node();
@@ -766,29 +1328,219 @@ where
             }
             _ => self.parse_pipe_left(),
         };
-        self.depth -= 1;
+        self.leave_nested();
         out
     }
 }
 
+/// The nesting depth of expressions `parse` allows, chosen somewhat arbitrarily
+pub const DEFAULT_DEPTH_LIMIT: usize = 512;
+
+/// Options for `parse_with_config`. Start from the default and change it with
+/// the setters, since more options may be added:
+///
+/// ```
+/// use rnix::{parser::ParseConfig, Root};
+///
+/// let config = ParseConfig::default().with_or_as_keyword(false).with_recover(false);
+/// assert!(Root::parse_with_config("a.b or c", config).ok().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ParseConfig {
+    /// The nesting depth of expressions allowed, see `parse_with_limit`
+    pub max_depth: usize,
+    /// Whether to accept the deprecated `let { ... }` syntax, which evaluates
+    /// to the `body` attribute of the set, without reporting
+    /// `ParseError::LegacyLet`
+    pub allow_legacy_let: bool,
+    /// Whether `or` is a keyword for the default of a select like `a.b or c`.
+    /// If disabled, it's an identifier like any other, so that `a.b or c`
+    /// applies `a.b` to `or` and `c`.
+    pub or_as_keyword: bool,
+    /// Whether to recover from errors to continue parsing. If disabled,
+    /// parsing stops at the first error, which is the only one reported.
+    /// Everything after it is put in an error node at the end of the root
+    /// without looking at it, so that broken files are rejected quickly.
+    pub recover: bool,
+}
+
+impl Default for ParseConfig {
+    /// The configuration `parse` uses
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_DEPTH_LIMIT,
+            allow_legacy_let: true,
+            or_as_keyword: true,
+            recover: true,
+        }
+    }
+}
+
+impl ParseConfig {
+    /// Set `max_depth`
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+    /// Set `allow_legacy_let`
+    pub fn with_allow_legacy_let(mut self, allow_legacy_let: bool) -> Self {
+        self.allow_legacy_let = allow_legacy_let;
+        self
+    }
+    /// Set `or_as_keyword`
+    pub fn with_or_as_keyword(mut self, or_as_keyword: bool) -> Self {
+        self.or_as_keyword = or_as_keyword;
+        self
+    }
+    /// Set `recover`
+    pub fn with_recover(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+}
+
 /// Parse tokens into an AST
 pub fn parse<'s, I>(iter: I) -> (GreenNode, Vec<ParseError>)
 where
     I: Iterator<Item = Token<'s>>,
 {
-    let mut parser = Parser::new(iter);
+    parse_with_config(iter, ParseConfig::default())
+}
+
+/// Parse tokens into an AST, allowing expressions to be nested at most
+/// `max_depth` levels deep
+pub fn parse_with_limit<'s, I>(iter: I, max_depth: usize) -> (GreenNode, Vec<ParseError>)
+where
+    I: Iterator<Item = Token<'s>>,
+{
+    parse_with_config(iter, ParseConfig { max_depth, ..Default::default() })
+}
+
+/// Parse tokens into an AST with the given options
+pub fn parse_with_config<'s, I>(iter: I, config: ParseConfig) -> (GreenNode, Vec<ParseError>)
+where
+    I: Iterator<Item = Token<'s>>,
+{
+    run(Parser::new(iter, config, GreenNodeBuilder::new()))
+}
+
+/// Parse tokens into an AST with the given options, deduplicating the nodes
+/// and tokens of the tree with those already in `cache`
+pub fn parse_with_cache<'s, I>(
+    iter: I,
+    config: ParseConfig,
+    cache: &mut NodeCache,
+) -> (GreenNode, Vec<ParseError>)
+where
+    I: Iterator<Item = Token<'s>>,
+{
+    run(Parser::new(iter, config, GreenNodeBuilder::with_cache(cache)))
+}
+
+/// The number of times `parse_text` parses again to recover from unclosed
+/// interpolations, which bounds the time spent on files with many of them
+const MAX_INTERPOL_RECOVERIES: usize = 8;
+
+/// Tokenize and parse `s` like `parse_with_config`, or `parse_with_cache` with
+/// a `cache`. A `${` without a `}` in a string makes the rest of the string
+/// and every `"` after it lex differently, so the tokenizer can't recover from
+/// that by itself. Instead, `s` is parsed again with the interpolation ended
+/// where the string most likely ends, see `interpol_end`. Code without such
+/// errors is only parsed once.
+pub(crate) fn parse_text(
+    s: &str,
+    config: ParseConfig,
+    mut cache: Option<&mut NodeCache>,
+) -> (GreenNode, Vec<ParseError>) {
+    let mut ends = Vec::new();
+    loop {
+        let tokens = Tokenizer::new(s).with_interpol_ends(ends.clone());
+        let (green, errors) = match cache.as_deref_mut() {
+            Some(cache) => parse_with_cache(tokens, config, cache),
+            None => parse_with_config(tokens, config),
+        };
+        if ends.len() == MAX_INTERPOL_RECOVERIES {
+            return (green, errors);
+        }
+        let root = SyntaxNode::new_root(green.clone());
+        let end = errors.iter().find_map(|error| match error {
+            ParseError::UnclosedInterpolation { open } => {
+                interpol_end(&root, *open).filter(|end| !ends.contains(end))
+            }
+            _ => None,
+        });
+        match end {
+            Some(end) => ends.push(end),
+            None => return (green, errors),
+        }
+    }
+}
+
+/// Where to end the unclosed interpolation opened at `open` in a string,
+/// which is the first of these after it:
+/// - a `"` or `''` like the one the string started with, that starts another
+///   string which isn't terminated, or spans several lines if double-quoted
+/// - a newline, if the string is double-quoted
+fn interpol_end(root: &SyntaxNode, open: TextRange) -> Option<usize> {
+    let start = root.token_at_offset(open.start()).right_biased()?;
+    let string = start.parent()?.parent().filter(|node| node.kind() == NODE_STRING)?;
+    let double_quoted = string.first_token()?.text() == "\"";
+    let mut token = start.next_token();
+    while let Some(current) = token {
+        let ends_string = match current.kind() {
+            TOKEN_STRING_START if (current.text() == "\"") == double_quoted => {
+                current.parent().is_some_and(|string| {
+                    let terminated =
+                        string.last_token().is_some_and(|last| last.kind() == TOKEN_STRING_END);
+                    !terminated || (double_quoted && string.text().contains_char('\n'))
+                })
+            }
+            TOKEN_WHITESPACE => double_quoted && current.text().contains('\n'),
+            _ => false,
+        };
+        if ends_string {
+            return Some(usize::from(current.text_range().start()));
+        }
+        token = current.next_token();
+    }
+    None
+}
+
+fn run<'s, I>(mut parser: Parser<'s, '_, I>) -> (GreenNode, Vec<ParseError>)
+where
+    I: Iterator<Item = Token<'s>>,
+{
     parser.builder.start_node(NixLanguage::kind_to_raw(NODE_ROOT));
     parser.parse_expr();
     parser.eat_trivia();
     if parser.peek().is_some() {
         let start = parser.start_error_node();
-        while parser.peek().is_some() {
+        // Error tokens are reported by `bump` already, so extra input of just those isn't again
+        let mut only_errors = true;
+        while let Some(kind) = parser.peek() {
+            only_errors &= kind == TOKEN_ERROR;
             parser.bump();
         }
         let end = parser.finish_error_node();
-        parser.errors.push(ParseError::UnexpectedExtra(TextRange::new(start, end)));
+        if !only_errors {
+            parser.errors.push(ParseError::UnexpectedExtra { range: TextRange::new(start, end) });
+        }
         parser.eat_trivia();
     }
+    if parser.stopped() {
+        // Errors reported while unwinding only follow from stopping
+        parser.errors.truncate(1);
+        parser.drain_trivia_buffer();
+        if let Some((kind, s)) = parser.try_next() {
+            parser.start_error_node();
+            parser.manual_bump(s, kind);
+            while let Some((kind, s)) = parser.try_next() {
+                parser.manual_bump(s, kind);
+            }
+            parser.finish_error_node();
+        }
+    }
     parser.builder.finish_node();
     (parser.builder.finish(), parser.errors)
 }
@@ -0,0 +1,229 @@
+//! Incremental reparsing: reuse as much of an existing tree as possible after an edit
+
+use rowan::{GreenNode, GreenToken, Language, NodeOrToken, TextRange};
+
+use crate::{
+    parser::{self, ParseConfig},
+    tokenize,
+    tokenizer::Tokenizer,
+    NixLanguage, Parse, Root,
+    SyntaxKind::{self, *},
+    SyntaxNode,
+};
+
+/// A replacement of the text in `range` by `replacement`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextEdit {
+    pub range: TextRange,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    /// Apply the edit to `text`, which is assumed to start at offset 0
+    pub fn apply(&self, text: &mut String) {
+        text.replace_range(std::ops::Range::<usize>::from(self.range), &self.replacement);
+    }
+
+    /// Apply the edit to `text`, which starts at offset `range.start()` of the edited file
+    fn apply_within(&self, text: &str, range: TextRange) -> String {
+        let mut text = text.to_string();
+        let relative = self.range.checked_sub(range.start()).unwrap();
+        text.replace_range(std::ops::Range::<usize>::from(relative), &self.replacement);
+        text
+    }
+}
+
+impl Parse<Root> {
+    /// Apply `edit` to the parsed text and parse the result, reusing the
+    /// unchanged parts of the tree where possible.
+    ///
+    /// Only trees without errors are reparsed incrementally. The edit must
+    /// either stay within a single whitespace, comment, identifier, or
+    /// number token that it doesn't change the kind of, or stay strictly
+    /// within the delimiters of an attrset, list, or parenthesized
+    /// expression. Everything else falls back to a full reparse. Either way,
+    /// the text is parsed with the same `ParseConfig` as before.
+    pub fn reparse(&self, edit: &TextEdit) -> Parse<Root> {
+        if self.errors.is_empty() {
+            let root = self.syntax();
+            if let Some(green) =
+                reparse_token(&root, edit).or_else(|| reparse_block(&root, edit, self.config))
+            {
+                return Parse { config: self.config, ..Parse::from_parts(green, Vec::new()) };
+            }
+        }
+
+        let mut text = self.syntax().to_string();
+        edit.apply(&mut text);
+        Root::parse_with_config(&text, self.config)
+    }
+}
+
+/// Tokens that are lexed the same regardless of the surrounding context
+fn is_relexable(kind: SyntaxKind) -> bool {
+    matches!(kind, TOKEN_WHITESPACE | TOKEN_COMMENT | TOKEN_IDENT | TOKEN_INTEGER | TOKEN_FLOAT)
+}
+
+fn reparse_token(root: &SyntaxNode, edit: &TextEdit) -> Option<GreenNode> {
+    let token = root.covering_element(edit.range).into_token()?;
+    if !is_relexable(token.kind()) {
+        return None;
+    }
+    let text = edit.apply_within(token.text(), token.text_range());
+
+    // Lex the new text together with its neighbours, to make sure it won't
+    // get merged with either of them.
+    let prev = token.prev_token();
+    let next = token.next_token();
+    let input = format!(
+        "{}{}{}",
+        prev.as_ref().map_or("", |t| t.text()),
+        text,
+        next.as_ref().map_or("", |t| t.text())
+    );
+    let expected = prev
+        .iter()
+        .map(|t| (t.kind(), t.text()))
+        .chain(Some((token.kind(), text.as_str())))
+        .chain(next.iter().map(|t| (t.kind(), t.text())));
+    if !tokenize(&input).into_iter().eq(expected) {
+        return None;
+    }
+
+    Some(token.replace_with(GreenToken::new(NixLanguage::kind_to_raw(token.kind()), &text)))
+}
+
+fn reparse_block(root: &SyntaxNode, edit: &TextEdit, config: ParseConfig) -> Option<GreenNode> {
+    let ancestors = match root.covering_element(edit.range) {
+        NodeOrToken::Node(node) => node.ancestors(),
+        NodeOrToken::Token(token) => token.parent()?.ancestors(),
+    };
+    let node = ancestors
+        .filter(|node| matches!(node.kind(), NODE_ATTR_SET | NODE_LIST | NODE_PAREN))
+        .find(|node| is_strictly_inside(node, edit.range))?;
+
+    // Every level of nesting the parser is in when it gets to the block,
+    // besides the one parsing the block itself, is within a different one of
+    // its ancestors below the root, which bounds how much of the depth limit
+    // is left for the block. If this overestimates the depth, hitting the
+    // limit falls back to a full reparse.
+    let depth = node.ancestors().skip(1).filter(|node| node.kind() != NODE_ROOT).count();
+    let config = ParseConfig { max_depth: config.max_depth.checked_sub(depth)?, ..config };
+    let text = edit.apply_within(&node.to_string(), node.text_range());
+    let (green, errors) = parser::parse_with_config(Tokenizer::new(&text), config);
+    if !errors.is_empty() {
+        return None;
+    }
+
+    // The new text must still be exactly one node of the same kind, with no
+    // surrounding trivia or trailing select/application.
+    let new_root = SyntaxNode::new_root(green);
+    let mut children = new_root.children_with_tokens();
+    let new_node = match (children.next(), children.next()) {
+        (Some(NodeOrToken::Node(new_node)), None) if new_node.kind() == node.kind() => new_node,
+        _ => return None,
+    };
+
+    Some(node.replace_with(new_node.green().into_owned()))
+}
+
+/// Whether `range` is within `node` without touching its first or last token
+fn is_strictly_inside(node: &SyntaxNode, range: TextRange) -> bool {
+    let (first, last) = match (node.first_token(), node.last_token()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return false,
+    };
+    first.text_range().end() <= range.start() && range.end() <= last.text_range().start()
+}
+
+#[cfg(test)]
+mod tests {
+    use rowan::{TextRange, TextSize};
+
+    use super::*;
+
+    fn edit(text: &str, range: (u32, u32), replacement: &str) -> (Parse<Root>, TextEdit) {
+        let range = TextRange::new(TextSize::from(range.0), TextSize::from(range.1));
+        (Root::parse(text), TextEdit { range, replacement: replacement.to_string() })
+    }
+
+    fn check(text: &str, range: (u32, u32), replacement: &str) {
+        check_with_config(ParseConfig::default(), text, range, replacement)
+    }
+
+    fn check_with_config(config: ParseConfig, text: &str, range: (u32, u32), replacement: &str) {
+        let (_, edit) = edit(text, range, replacement);
+        let incremental = Root::parse_with_config(text, config).reparse(&edit);
+
+        let mut text = text.to_string();
+        edit.apply(&mut text);
+        let full = Root::parse_with_config(&text, config);
+
+        assert_eq!(format!("{:#?}", incremental.syntax()), format!("{:#?}", full.syntax()));
+        assert_eq!(incremental.errors(), full.errors());
+        assert_eq!(incremental.config(), config);
+    }
+
+    #[test]
+    fn token() {
+        let (parse, e) = edit("{ foo = 1; }", (5, 5), "x");
+        assert!(reparse_token(&parse.syntax(), &e).is_some());
+        check("{ foo = 1; }", (5, 5), "x");
+        check("{ foo = 1; }", (8, 9), "42");
+        check("a  +  b # comment", (10, 17), "# other comment");
+
+        // Changes the kind of the token
+        let (parse, e) = edit("{ foo = 1; }", (8, 9), "1.5");
+        assert!(reparse_token(&parse.syntax(), &e).is_none());
+        check("{ foo = 1; }", (8, 9), "1.5");
+        check("{ foo = 1; }", (2, 5), "in");
+        // Merges with the next token
+        check("a b", (1, 2), "");
+        // Swallows the next token
+        check("a # comment\nb", (11, 11), " still");
+    }
+
+    #[test]
+    fn block() {
+        let (parse, e) = edit("{ a = { b = 1; }; c = [ 1 ]; }", (24, 24), " 2");
+        assert!(reparse_block(&parse.syntax(), &e, parse.config()).is_some());
+        check("{ a = { b = 1; }; c = [ 1 ]; }", (24, 24), " 2");
+        check("{ a = { b = 1; }; c = [ 1 ]; }", (14, 14), " d = 2;");
+        check("f (a + b) c", (3, 8), "x: x");
+
+        // Touches the delimiters
+        let (parse, e) = edit("{ a = [ 1 ]; }", (13, 14), "");
+        assert!(reparse_block(&parse.syntax(), &e, parse.config()).is_none());
+        check("{ a = [ 1 ]; }", (13, 14), "");
+        check("{ a = [ 1 ]; }", (6, 7), "(");
+        // Makes the block unbalanced
+        check("{ a = [ 1 ]; }", (9, 9), "]");
+        check("{ a = [ 1 ]; }", (9, 9), "\"");
+    }
+
+    #[test]
+    fn config() {
+        let or_ident = ParseConfig { or_as_keyword: false, ..Default::default() };
+        check_with_config(or_ident, "{ a = [ b.c or d ]; }", (9, 9), "x");
+        check_with_config(or_ident, "{ a = [ b.c or d ]; }", (6, 7), "(");
+
+        let no_legacy_let = ParseConfig { allow_legacy_let: false, ..Default::default() };
+        check_with_config(no_legacy_let, "{ a = [ 1 ]; }", (8, 9), "let { body = 1; }");
+
+        // The depth limit counts the levels outside of the reparsed block
+        let shallow = ParseConfig { max_depth: 6, ..Default::default() };
+        let (parse, e) = edit("[ [ [ 1 ] ] ]", (6, 7), "[ [ 1 ] ]");
+        let parse = Root::parse_with_config(&parse.source(), shallow);
+        assert!(reparse_block(&parse.syntax(), &e, shallow).is_some());
+        check_with_config(shallow, "[ [ [ 1 ] ] ]", (6, 7), "[ [ 1 ] ]");
+        let (_, e) = edit("[ [ [ 1 ] ] ]", (6, 7), "[ [ [ 1 ] ] ]");
+        assert!(reparse_block(&parse.syntax(), &e, shallow).is_none());
+        check_with_config(shallow, "[ [ [ 1 ] ] ]", (6, 7), "[ [ [ 1 ] ] ]");
+    }
+
+    #[test]
+    fn errors() {
+        check("{ a = ; b = 1; }", (12, 13), "2");
+        check("{ a = 1; b = 1; }", (6, 7), "");
+    }
+}
@@ -0,0 +1,176 @@
+//! Resolution of identifiers to the place they're bound at
+
+use rowan::ast::AstNode;
+
+use crate::{
+    ast::{self, Entry, HasEntry, Param},
+    SyntaxKind::*,
+    SyntaxNode,
+};
+
+/// Where a variable is bound
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    /// An attribute of a `let ... in` or legacy `let { ... }`. The node is
+    /// the `Attr` of `name = ...;` or `name.path = ...;`, or the `Ident` of
+    /// `inherit name;`.
+    LetBinding(SyntaxNode),
+    /// An attribute of a `rec { ... }`, with the same node as `LetBinding`
+    RecAttr(SyntaxNode),
+    /// A function parameter. The node is the `Ident` of `name: ...`,
+    /// `{ name }: ...` or `name@{ }: ...`.
+    LambdaParam(SyntaxNode),
+    /// No binding is in scope, but the variable could come from the namespace
+    /// of this `with`, the innermost one around it. What that namespace
+    /// contains is only known when evaluating it.
+    With(ast::With),
+}
+
+/// Find the binding the variable `ident` refers to, or `None` if it isn't in
+/// scope at all, e.g. a builtin like `toString`. Returns `None` as well if
+/// `ident` isn't a variable but an attribute name like `b` in `a.b` or the
+/// name of a binding itself.
+///
+/// Like in Nix, bindings of a `with` have a lower priority than all other
+/// bindings, so `let a = 1; in with { a = 2; }; a` resolves to the `let`.
+pub fn resolve(ident: &ast::Ident) -> Option<Binding> {
+    let name = ident.ident_token()?.text().to_string();
+    if !is_variable(ident.syntax()) {
+        return None;
+    }
+
+    let mut with = None;
+    let mut child = ident.syntax().clone();
+    for parent in ident.syntax().ancestors().skip(1) {
+        // `inherit name;` refers to `name` outside of the set it binds it in
+        let inherited = ast::Inherit::cast(child.clone()).is_some_and(|it| it.from().is_none());
+        let binding = match parent.kind() {
+            NODE_LET_IN if !inherited => {
+                find_entry(&ast::LetIn::cast(parent.clone())?, &name).map(Binding::LetBinding)
+            }
+            NODE_LEGACY_LET if !inherited => {
+                find_entry(&ast::LegacyLet::cast(parent.clone())?, &name).map(Binding::LetBinding)
+            }
+            NODE_ATTR_SET if !inherited => {
+                let set = ast::AttrSet::cast(parent.clone())?;
+                set.is_rec().then(|| find_entry(&set, &name)).flatten().map(Binding::RecAttr)
+            }
+            NODE_LAMBDA => {
+                find_param(&ast::Lambda::cast(parent.clone())?, &name).map(Binding::LambdaParam)
+            }
+            NODE_WITH => {
+                let it = ast::With::cast(parent.clone())?;
+                if with.is_none() && it.namespace().is_none_or(|ns| *ns.syntax() != child) {
+                    with = Some(it);
+                }
+                None
+            }
+            _ => None,
+        };
+        if binding.is_some() {
+            return binding;
+        }
+        child = parent;
+    }
+    with.map(Binding::With)
+}
+
+/// Whether `ident` is a use of a variable, instead of e.g. an attribute name
+fn is_variable(ident: &SyntaxNode) -> bool {
+    let Some(parent) = ident.parent() else { return true };
+    match parent.kind() {
+        NODE_ATTRPATH | NODE_IDENT_PARAM | NODE_PAT_BIND => false,
+        NODE_INHERIT => ast::Inherit::cast(parent).is_some_and(|it| it.from().is_none()),
+        NODE_PAT_ENTRY => parent.first_child().as_ref() != Some(ident),
+        _ => true,
+    }
+}
+
+fn find_entry(set: &impl HasEntry, name: &str) -> Option<SyntaxNode> {
+    set.entries().find_map(|entry| {
+        let attr = match entry {
+            Entry::Inherit(inherit) => {
+                inherit.attrs().find(|attr| attr.static_key().as_deref() == Some(name))
+            }
+            Entry::AttrpathValue(attrpath_value) => attrpath_value
+                .attrpath()?
+                .attrs()
+                .next()
+                .filter(|attr| attr.static_key().as_deref() == Some(name)),
+        };
+        attr.map(|attr| attr.syntax().clone())
+    })
+}
+
+fn find_param(lambda: &ast::Lambda, name: &str) -> Option<SyntaxNode> {
+    let idents: Vec<ast::Ident> = match lambda.param()? {
+        Param::IdentParam(param) => param.ident().into_iter().collect(),
+        Param::Pattern(pattern) => pattern
+            .entries()
+            .filter_map(|entry| entry.ident())
+            .chain(pattern.at_binding())
+            .collect(),
+    };
+    idents
+        .into_iter()
+        .find(|ident| ident.ident_token().is_some_and(|token| token.text() == name))
+        .map(|ident| ident.syntax().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Root, TextSize};
+
+    /// Resolve the identifier at the `n`th occurrence of `name` and return the
+    /// offset of the binding, or of the `with` keyword
+    fn resolve_nth(s: &str, name: &str, n: usize) -> Option<(&'static str, TextSize)> {
+        let root = Root::parse(s).ok().unwrap();
+        let ident = root
+            .syntax()
+            .descendants()
+            .filter_map(ast::Ident::cast)
+            .filter(|ident| ident.syntax().text() == name)
+            .nth(n)
+            .unwrap();
+        Some(match resolve(&ident)? {
+            Binding::LetBinding(node) => ("let", node.text_range().start()),
+            Binding::RecAttr(node) => ("rec", node.text_range().start()),
+            Binding::LambdaParam(node) => ("param", node.text_range().start()),
+            Binding::With(with) => ("with", with.syntax().text_range().start()),
+        })
+    }
+
+    #[test]
+    fn nested_let() {
+        let s = "let a = 1; in let a = 2; b = a; in a + b";
+        assert_eq!(resolve_nth(s, "a", 0), None);
+        assert_eq!(resolve_nth(s, "a", 2), Some(("let", 18.into())));
+        assert_eq!(resolve_nth(s, "a", 3), Some(("let", 18.into())));
+        assert_eq!(resolve_nth(s, "b", 1), Some(("let", 25.into())));
+
+        let s = "let a = 1; in let inherit a; in a";
+        assert_eq!(resolve_nth(s, "a", 1), Some(("let", 4.into())));
+        assert_eq!(resolve_nth(s, "a", 2), Some(("let", 26.into())));
+    }
+
+    #[test]
+    fn params_and_rec() {
+        let s = "a: { a, b ? a }: rec { c = a; d = c; e = x.c; }";
+        assert_eq!(resolve_nth(s, "a", 2), Some(("param", 5.into())));
+        assert_eq!(resolve_nth(s, "a", 3), Some(("param", 5.into())));
+        assert_eq!(resolve_nth(s, "c", 1), Some(("rec", 23.into())));
+        assert_eq!(resolve_nth(s, "c", 2), None);
+        assert_eq!(resolve_nth("{ c = 1; d = c; }", "c", 1), None);
+    }
+
+    #[test]
+    fn with() {
+        let s = "with a; let b = 1; in with c; [ b d a ]";
+        assert_eq!(resolve_nth(s, "b", 1), Some(("let", 12.into())));
+        assert_eq!(resolve_nth(s, "d", 0), Some(("with", 22.into())));
+        assert_eq!(resolve_nth(s, "a", 0), None);
+        assert_eq!(resolve_nth(s, "a", 1), Some(("with", 22.into())));
+        assert_eq!(resolve_nth(s, "c", 0), Some(("with", 0.into())));
+    }
+}
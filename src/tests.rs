@@ -1,13 +1,36 @@
-use std::{ffi::OsStr, fmt::Write, fs, path::PathBuf};
+use std::{
+    ffi::OsStr,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
 
-use expect_test::expect_file;
+use expect_test::{expect, expect_file};
 use rowan::ast::AstNode;
 
 use crate::{
     ast::{self, HasEntry},
-    tokenize, Root, SyntaxKind,
+    format,
+    parser::{self, ParseConfig, ParseError},
+    tokenize,
+    tokenizer::Tokenizer,
+    EncodingError, GreenNode, NodeCache, Root, SyntaxKind, SyntaxNode, TextRange, TextSize,
 };
 
+/// The expression of `s` as a `T`, panicking if `s` has errors or isn't one
+pub(crate) fn parse_as<T: TryFrom<ast::Expr>>(s: &str) -> T
+where
+    T::Error: std::fmt::Debug,
+{
+    let root = Root::parse(s).ok().unwrap();
+    T::try_from(root.expr().unwrap()).unwrap()
+}
+
+/// The range from `start` to `end`
+pub(crate) fn range(start: u32, end: u32) -> TextRange {
+    TextRange::new(start.into(), end.into())
+}
+
 #[test]
 fn interpolation() {
     let root = ast::Root::parse(include_str!("../test_data/parser/success/interpolation.nix"))
@@ -32,6 +55,32 @@ fn interpolation() {
         && s3 == "\n" => (),
     parts => panic!("did not match: {:#?}", parts)
 }
+    let exprs: Vec<_> = value
+        .normalized_parts()
+        .into_iter()
+        .filter_map(|part| match part {
+            ast::InterpolPart::Interpolation(interpol) => Some(interpol.expr()?.syntax().kind()),
+            ast::InterpolPart::Literal(_) => None,
+        })
+        .collect();
+    assert_eq!(exprs, [SyntaxKind::NODE_SELECT, SyntaxKind::NODE_STRING]);
+}
+
+#[test]
+fn root_expr() {
+    let root = Root::parse("# file\n{ a = 1; } # end\n").ok().unwrap();
+    assert_eq!(root.expr().unwrap().syntax().text(), "{ a = 1; }");
+
+    // Nix rejects files without an expression
+    for s in ["", " # only\n/* comments */\n"] {
+        let parse = Root::parse(s);
+        assert_eq!(parse.errors(), [ParseError::UnexpectedEOF { expected: Vec::new() }]);
+        assert!(parse.tree().expr().is_none());
+    }
+
+    let parse = Root::parse("[ 1 ]; 2");
+    assert!(matches!(parse.errors(), [ParseError::UnexpectedExtra { .. }]));
+    assert_eq!(parse.tree().expr().unwrap().syntax().text(), "[ 1 ]");
 }
 
 #[test]
@@ -51,6 +100,458 @@ fn inherit() {
     assert!(children.next().is_none());
 }
 
+#[test]
+fn bound_names() {
+    let root = ast::Root::parse(include_str!("../test_data/parser/success/let_bindings.nix"))
+        .ok()
+        .unwrap();
+    let let_in = ast::LetIn::try_from(root.expr().unwrap()).unwrap();
+    let names: Vec<String> = let_in.bound_names().collect();
+    assert_eq!(names, ["a", "b", "d", "f", "g", "h", "i"]);
+
+    let root = ast::Root::parse("{ x = 1; inherit y; }").ok().unwrap();
+    let set = ast::AttrSet::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(set.bound_names().collect::<Vec<_>>(), ["x", "y"]);
+}
+
+#[test]
+fn let_inherit() {
+    for (s, from) in [("let inherit a; in a", None), ("let inherit (x) a b; in a", Some("x"))] {
+        let let_in = parse_as::<ast::LetIn>(s);
+        let inherits: Vec<_> = let_in.inherits().collect();
+        assert_eq!(inherits.len(), 1);
+        let inherit_from = inherits[0].from().and_then(|from| from.expr());
+        assert_eq!(inherit_from.map(|expr| expr.syntax().to_string()).as_deref(), from);
+        assert_eq!(let_in.bound_names().next().as_deref(), Some("a"));
+
+        // The body refers to the inherited name
+        let body = ast::Ident::try_from(let_in.body().unwrap()).unwrap();
+        let binding = crate::scope::resolve(&body).unwrap();
+        let ident = inherits[0].idents().next().unwrap();
+        assert_eq!(binding, crate::scope::Binding::LetBinding(ident.syntax().clone()));
+    }
+    let let_in = parse_as::<ast::LetIn>("let inherit (x) a b; c = 1; in a");
+    assert_eq!(let_in.bound_names().count(), 3);
+}
+
+#[test]
+fn implication() {
+    use ast::BinOpKind::*;
+
+    fn bin_op(expr: ast::Expr) -> (ast::Expr, ast::BinOpKind, ast::Expr) {
+        let op = ast::BinOp::try_from(expr).unwrap();
+        (op.lhs().unwrap(), op.operator().unwrap(), op.rhs().unwrap())
+    }
+    fn ident(expr: &ast::Expr) -> String {
+        ast::Ident::try_from(expr.clone()).unwrap().to_string()
+    }
+
+    // a -> (b -> c)
+    let (a, op, rhs) = bin_op(parse_as("a -> b -> c"));
+    assert_eq!((ident(&a), op), ("a".into(), Implication));
+    let (b, op, c) = bin_op(rhs);
+    assert_eq!((ident(&b), op, ident(&c)), ("b".into(), Implication, "c".into()));
+
+    // (a || b) -> c, however the `||` is placed
+    let (lhs, op, c) = bin_op(parse_as("a || b -> c"));
+    assert_eq!((op, ident(&c)), (Implication, "c".into()));
+    assert_eq!(bin_op(lhs).1, Or);
+    let (a, op, rhs) = bin_op(parse_as("a -> b || c && d"));
+    assert_eq!((ident(&a), op), ("a".into(), Implication));
+    assert_eq!(bin_op(rhs).1, Or);
+}
+
+#[test]
+fn select_path_string() {
+    fn path(s: &str) -> Option<String> {
+        parse_as::<ast::Select>(s).path_string()
+    }
+
+    assert_eq!(path("a.b.c").as_deref(), Some("a.b.c"));
+    assert_eq!(path("(f x).b").as_deref(), Some("b"));
+    assert_eq!(path(r#"a."b c".or or d"#).as_deref(), Some("a.b c.or"));
+    assert_eq!(path("a.${d}.c"), None);
+    assert_eq!(path(r#"a."${d}""#), None);
+}
+
+#[test]
+fn static_key() {
+    fn keys(s: &str) -> Vec<Option<String>> {
+        let set = parse_as::<ast::AttrSet>(s);
+        let value = set.attrpath_values().next().unwrap();
+        value.attrpath().unwrap().attrs().map(|attr| attr.static_key()).collect()
+    }
+
+    assert_eq!(keys("{ foo = 1; }"), [Some("foo".into())]);
+    assert_eq!(keys(r#"{ "foo bar" = 1; }"#), [Some("foo bar".into())]);
+    assert_eq!(keys("{ ${x} = 1; }"), [None]);
+    assert_eq!(
+        keys(r#"{ a."b${c}".${d}.or = 1; }"#),
+        [Some("a".into()), None, None, Some("or".into())]
+    );
+}
+
+#[test]
+fn select_or() {
+    let root = ast::Root::parse("x.y or z").ok().unwrap();
+    let select = ast::Select::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(select.or_token().unwrap().text(), "or");
+    assert_eq!(select.default_expr().unwrap().syntax().text(), "z");
+
+    let root = ast::Root::parse("{ or = 1; }.or").ok().unwrap();
+    let select = ast::Select::try_from(root.expr().unwrap()).unwrap();
+    assert!(select.or_token().is_none());
+    assert!(select.default_expr().is_none());
+    let attr = select.attrpath().unwrap().attrs().next().unwrap();
+    assert_eq!(ast::Ident::try_from(attr).unwrap().ident_token().unwrap().text(), "or");
+
+    let root = ast::Root::parse("{ or = 1; }.or or 2").ok().unwrap();
+    let select = ast::Select::try_from(root.expr().unwrap()).unwrap();
+    assert!(select.or_token().is_some());
+    assert_eq!(select.default_expr().unwrap().syntax().text(), "2");
+}
+
+#[test]
+fn rec() {
+    fn set(s: &str) -> ast::AttrSet {
+        let root = ast::Root::parse(s).tree();
+        ast::AttrSet::try_from(root.expr().unwrap()).unwrap()
+    }
+
+    let rec = set("rec { x = 1; }");
+    assert!(rec.is_rec());
+    assert_eq!(rec.rec_token().unwrap().text(), "rec");
+
+    let quoted = set(r#"{ "rec" = 1; x."rec" = 2; }"#);
+    assert!(!quoted.is_rec());
+    assert_eq!(quoted.bound_names().collect::<Vec<_>>(), ["rec", "x"]);
+    assert!(Root::parse(&quoted.to_string()).errors().is_empty());
+
+    // Not valid Nix, but the keyword within the binding must not make the set recursive
+    let unquoted = set("{ rec = 1; }");
+    assert!(!unquoted.is_rec());
+    assert!(unquoted.rec_token().is_none());
+}
+
+#[test]
+fn assert() {
+    let root = ast::Root::parse("assert a; assert b == c; d").ok().unwrap();
+    let outer = ast::Assert::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(outer.condition().unwrap().syntax().text(), "a");
+    assert!(outer.semicolon_token().is_some());
+
+    let inner = ast::Assert::try_from(outer.body().unwrap()).unwrap();
+    assert_eq!(inner.condition().unwrap().syntax().text(), "b == c");
+    assert_eq!(inner.body().unwrap().syntax().text(), "d");
+}
+
+#[test]
+fn with() {
+    let root = ast::Root::parse("with a; with b.c; d").ok().unwrap();
+    let outer = ast::With::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(outer.namespace().unwrap().syntax().text(), "a");
+
+    let inner = ast::With::try_from(outer.body().unwrap()).unwrap();
+    assert_eq!(inner.namespace().unwrap().syntax().text(), "b.c");
+    assert_eq!(inner.body().unwrap().syntax().text(), "d");
+}
+
+#[test]
+fn if_else() {
+    let root = ast::Root::parse("if a then b else c").ok().unwrap();
+    let if_else = ast::IfElse::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(if_else.condition().unwrap().syntax().text(), "a");
+    assert_eq!(if_else.body().unwrap().syntax().text(), "b");
+    assert_eq!(if_else.else_body().unwrap().syntax().text(), "c");
+    assert!(if_else.if_token().is_some());
+    assert!(if_else.then_token().is_some());
+    assert!(if_else.else_token().is_some());
+
+    let root = ast::Root::parse("if a then b").tree();
+    let if_else = ast::IfElse::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(if_else.condition().unwrap().syntax().text(), "a");
+    assert_eq!(if_else.body().unwrap().syntax().text(), "b");
+    assert!(if_else.else_token().is_none());
+    assert!(if_else.else_body().is_none());
+
+    let root = ast::Root::parse("if a").tree();
+    let if_else = ast::IfElse::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(if_else.condition().unwrap().syntax().text(), "a");
+    assert!(if_else.then_token().is_none());
+    assert!(if_else.body().is_none());
+    assert!(if_else.else_body().is_none());
+
+    // A missing part leaves its slot empty instead of the next part moving into it
+    let text = |expr: Option<ast::Expr>| expr.map(|expr| expr.syntax().to_string());
+    for (code, parts) in [
+        ("if then b else c", [None, Some("b"), Some("c")]),
+        ("if a then else c", [Some("a"), None, Some("c")]),
+        ("if a else c", [Some("a"), None, Some("c")]),
+        ("if a then b else", [Some("a"), Some("b"), None]),
+    ] {
+        let parse = ast::Root::parse(code);
+        assert_eq!(parse.errors().len(), 1, "{}", code);
+        let if_else = ast::IfElse::try_from(parse.tree().expr().unwrap()).unwrap();
+        let found = [text(if_else.condition()), text(if_else.body()), text(if_else.else_body())];
+        assert_eq!(found, parts.map(|part| part.map(String::from)), "{}", code);
+        assert!(if_else.else_token().is_some(), "{}", code);
+    }
+}
+
+#[test]
+fn lambda_arity() {
+    fn arity(s: &str) -> usize {
+        parse_as::<ast::Lambda>(s).arity()
+    }
+
+    assert_eq!(arity("a: b: c: a"), 3);
+    assert_eq!(arity("{x}: x"), 1);
+    assert_eq!(arity("{ a, ... }@args: b: a"), 2);
+    assert_eq!(arity("a: (b: a)"), 1);
+    assert_eq!(arity("a: let f = b: a; in f"), 1);
+}
+
+#[test]
+fn attrpath_components() {
+    fn components(path: ast::Attrpath) -> Vec<(Option<String>, TextRange)> {
+        path.components().map(|attr| (attr.static_key(), attr.syntax().text_range())).collect()
+    }
+    let root = ast::Root::parse("{ x.y.${z} = 1; }").ok().unwrap();
+    let set = ast::AttrSet::try_from(root.expr().unwrap()).unwrap();
+    let binding = set.attrpath_values().next().unwrap();
+    assert_eq!(
+        components(binding.attrpath().unwrap()),
+        [
+            (Some("x".to_string()), range(2, 3)),
+            (Some("y".to_string()), range(4, 5)),
+            (None, range(6, 10))
+        ]
+    );
+
+    let root = ast::Root::parse("a.x.\"y\".z").ok().unwrap();
+    let select = ast::Select::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(
+        components(select.attrpath().unwrap()),
+        [
+            (Some("x".to_string()), range(2, 3)),
+            (Some("y".to_string()), range(4, 7)),
+            (Some("z".to_string()), range(8, 9))
+        ]
+    );
+}
+
+#[test]
+fn expr_kinds() {
+    use rowan::Language;
+    use SyntaxKind::*;
+
+    let not_expr: Vec<_> = (0..SyntaxKind::__LAST as u16)
+        .map(|raw| crate::NixLanguage::kind_from_raw(rowan::SyntaxKind(raw)))
+        .filter(|kind| kind.name().starts_with("NODE_") && !ast::Expr::can_cast(*kind))
+        .collect();
+    assert_eq!(
+        not_expr,
+        [
+            NODE_ATTRPATH,
+            NODE_DYNAMIC,
+            NODE_INHERIT,
+            NODE_INHERIT_FROM,
+            NODE_INTERPOL,
+            NODE_IDENT_PARAM,
+            NODE_PATTERN,
+            NODE_PAT_BIND,
+            NODE_PAT_ENTRY,
+            NODE_ATTRPATH_VALUE,
+        ]
+    );
+
+    let root = Root::parse("{ a = f [ 1 ] (x: x.y or null); }").ok().unwrap();
+    let exprs: Vec<_> = root
+        .syntax()
+        .descendants()
+        .filter_map(ast::Expr::cast)
+        .map(|expr| match expr {
+            ast::Expr::AttrSet(_) => "set",
+            ast::Expr::Apply(_) => "apply",
+            ast::Expr::List(_) => "list",
+            ast::Expr::Literal(_) => "literal",
+            ast::Expr::Paren(_) => "paren",
+            ast::Expr::Lambda(_) => "lambda",
+            ast::Expr::Select(_) => "select",
+            ast::Expr::Ident(_) => "ident",
+            _ => "other",
+        })
+        .collect();
+    assert_eq!(
+        exprs,
+        [
+            "other", "set", "ident", "apply", "apply", "ident", "list", "literal", "paren",
+            "lambda", "ident", "select", "ident", "ident", "ident"
+        ]
+    );
+}
+
+#[test]
+fn operator_tokens() {
+    use rowan::Language;
+
+    let kinds = (0..SyntaxKind::__LAST as u16)
+        .map(|raw| crate::NixLanguage::kind_from_raw(rowan::SyntaxKind(raw)));
+    let (mut binary, mut unary) = (0, 0);
+    for kind in kinds {
+        if let Some(op) = ast::BinOpKind::from_kind(kind) {
+            assert_eq!(op.token(), kind);
+            assert_eq!(tokenize(&op.to_string()), [(kind, op.as_str())]);
+            binary += 1;
+        }
+        if let Some(op) = ast::UnaryOpKind::from_kind(kind) {
+            assert_eq!(op.token(), kind);
+            assert_eq!(tokenize(&op.to_string()), [(kind, op.as_str())]);
+            unary += 1;
+        }
+    }
+    assert_eq!((binary, unary), (17, 2));
+    assert_eq!(ast::BinOpKind::Update.to_string(), "//");
+    assert_eq!(ast::UnaryOpKind::Invert.to_string(), "!");
+}
+
+#[test]
+fn bin_op() {
+    let root = ast::Root::parse("1 - - 2").ok().unwrap();
+    let bin_op = ast::BinOp::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(bin_op.lhs().unwrap().syntax().text(), "1");
+    assert_eq!(bin_op.operator(), Some(ast::BinOpKind::Sub));
+    assert_eq!(bin_op.operator_token().unwrap().text_range(), TextRange::new(2.into(), 3.into()));
+    let rhs = ast::UnaryOp::try_from(bin_op.rhs().unwrap()).unwrap();
+    assert_eq!(rhs.operator(), Some(ast::UnaryOpKind::Negate));
+    assert_eq!(rhs.syntax().text(), "- 2");
+
+    let root = ast::Root::parse("1 +").tree();
+    let bin_op = ast::BinOp::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(bin_op.lhs().unwrap().syntax().text(), "1");
+    assert_eq!(bin_op.operator(), Some(ast::BinOpKind::Add));
+    assert!(bin_op.rhs().is_none());
+}
+
+#[test]
+fn list() {
+    assert!(parse_as::<ast::List>("[ ]").is_empty());
+    assert_eq!(parse_as::<ast::List>("[]").len(), 0);
+
+    let flat = parse_as::<ast::List>("[ 1 a \"b\" ]");
+    assert_eq!(flat.len(), 3);
+    assert!(!flat.is_empty());
+
+    let items: Vec<_> =
+        parse_as::<ast::List>("[ a b (c d) ]").items().map(|item| item.to_string()).collect();
+    assert_eq!(items, ["a", "b", "(c d)"]);
+}
+
+#[test]
+fn has_entry() {
+    fn count(set: &impl HasEntry) -> (usize, usize, usize) {
+        (set.entries().count(), set.attrpath_values().count(), set.inherits().count())
+    }
+
+    let root = ast::Root::parse("let a = 1; inherit b c; d.e = 2; in a").ok().unwrap();
+    assert_eq!(count(&ast::LetIn::try_from(root.expr().unwrap()).unwrap()), (3, 2, 1));
+
+    let root = ast::Root::parse("{ a = 1; inherit b c; d.e = 2; }").ok().unwrap();
+    assert_eq!(count(&ast::AttrSet::try_from(root.expr().unwrap()).unwrap()), (3, 2, 1));
+}
+
+#[test]
+fn inherit_idents() {
+    fn inherit(s: &str) -> ast::Inherit {
+        let set = parse_as::<ast::AttrSet>(s);
+        set.inherits().next().unwrap()
+    }
+
+    let plain = inherit("{ inherit a b; }");
+    assert!(plain.from().is_none());
+    let idents: Vec<_> = plain.idents().map(|ident| ident.to_string()).collect();
+    assert_eq!(idents, ["a", "b"]);
+
+    let from = inherit(r#"{ inherit (set) z "y" a; }"#);
+    assert_eq!(from.from().unwrap().expr().unwrap().syntax().text(), "set");
+    let idents: Vec<_> = from.idents().map(|ident| ident.to_string()).collect();
+    assert_eq!(idents, ["z", "a"]);
+    let keys: Vec<_> = from.attrs().filter_map(|attr| attr.static_key()).collect();
+    assert_eq!(keys, ["z", "y", "a"]);
+}
+
+#[test]
+fn lambda_body_after_default_lambda() {
+    let root = ast::Root::parse("{ f ? a: a }: f").ok().unwrap();
+    let lambda = ast::Lambda::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(lambda.body().unwrap().syntax().text(), "f");
+
+    let Some(ast::Param::Pattern(pattern)) = lambda.param() else { panic!("not a pattern") };
+    let entry = pattern.pat_entries().next().unwrap();
+    let default = ast::Lambda::try_from(entry.default().unwrap()).unwrap();
+    assert_eq!(default.syntax().text(), "a: a");
+    assert_eq!(default.body().unwrap().syntax().text(), "a");
+}
+
+#[test]
+fn inherit_from_expr() {
+    fn from(s: &str) -> ast::Expr {
+        let root = ast::Root::parse(s).ok().unwrap();
+        let inherit = root.syntax().descendants().find_map(ast::Inherit::cast).unwrap();
+        inherit.from().unwrap().expr().unwrap()
+    }
+
+    let select = ast::Select::try_from(from("{ inherit (pkgs.lib) foo bar; }")).unwrap();
+    assert_eq!(select.expr().unwrap().syntax().text(), "pkgs");
+    assert_eq!(select.attrpath().unwrap().syntax().text(), "lib");
+
+    let select = ast::Select::try_from(from("let inherit (a.b.c) x; in x")).unwrap();
+    assert_eq!(select.attrpath().unwrap().attrs().count(), 2);
+    assert!(matches!(from("{ inherit (import ./lib.nix { }) x; }"), ast::Expr::Apply(_)));
+    assert!(matches!(from("{ inherit ((x)) y; }"), ast::Expr::Paren(_)));
+}
+
+#[test]
+fn pattern() {
+    fn pattern(s: &str) -> ast::Pattern {
+        let lambda = parse_as::<ast::Lambda>(s);
+        match lambda.param().unwrap() {
+            ast::Param::Pattern(pattern) => pattern,
+            ast::Param::IdentParam(_) => unreachable!(),
+        }
+    }
+
+    for s in ["{ a, b ? 1, ... }@args: a", "args@{ a, b ? 1, ... }: a"] {
+        let pattern = pattern(s);
+        let entries: Vec<_> = pattern.entries().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name().as_deref(), Some("a"));
+        assert!(!entries[0].has_default());
+        assert!(entries[0].default().is_none());
+        assert_eq!(entries[1].name().as_deref(), Some("b"));
+        assert!(entries[1].has_default());
+        assert_eq!(entries[1].default().unwrap().syntax().text(), "1");
+        assert!(pattern.ellipsis());
+        assert_eq!(pattern.at_binding().unwrap().syntax().text(), "args");
+    }
+
+    let pattern = pattern("{ a }: a");
+    assert!(!pattern.ellipsis());
+    assert!(pattern.at_binding().is_none());
+}
+
+#[test]
+fn apply_flatten() {
+    fn flatten(s: &str) -> (String, Vec<String>) {
+        let (head, args) = parse_as::<ast::Apply>(s).flatten();
+        (head.to_string(), args.iter().map(|arg| arg.to_string()).collect())
+    }
+
+    assert_eq!(flatten("f a b c"), ("f".to_string(), vec!["a".into(), "b".into(), "c".into()]));
+    assert_eq!(flatten("(f a) (g b)"), ("(f a)".to_string(), vec!["(g b)".into()]));
+    assert_eq!(flatten("x.f { } [ 1 ]"), ("x.f".to_string(), vec!["{ }".into(), "[ 1 ]".into()]));
+}
+
 #[test]
 fn math() {
     let root = ast::Root::parse(include_str!("../test_data/parser/success/math.nix")).ok().unwrap();
@@ -65,12 +566,687 @@ fn math() {
     assert_eq!(rhs.operator().unwrap(), ast::BinOpKind::Mul);
 }
 
+#[test]
+fn ok_all() {
+    let errors = Root::parse(include_str!("../test_data/parser/error/missing_semicolons.nix"))
+        .ok_all()
+        .unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(errors[0], ParseError::Unexpected { found: SyntaxKind::TOKEN_IDENT, .. }));
+    assert!(matches!(errors[1], ParseError::Unexpected { found: SyntaxKind::TOKEN_IDENT, .. }));
+
+    assert!(Root::parse("{ a = 1; }").ok_all().is_ok());
+}
+
+#[test]
+fn error_expected() {
+    let errors = Root::parse("{ a = 1 b = 2; }").ok_all().unwrap_err();
+    assert_eq!(errors[0].range(), Some(TextRange::new(8.into(), 9.into())));
+    assert_eq!(errors[0].expected(), [T![;]]);
+
+    let errors = Root::parse("{ a = 1 }").ok_all().unwrap_err();
+    assert!(matches!(errors[0], ParseError::Unexpected { found: T!['}'], .. }));
+    assert_eq!(errors[0].expected(), [T![;]]);
+
+    let errors = Root::parse("{ a = 1").ok_all().unwrap_err();
+    assert_eq!(errors[0].range(), None);
+    assert_eq!(errors[0].expected(), [T![;]]);
+    assert_eq!(errors[0].to_string(), "unexpected end of file, wanted any of [TOKEN_SEMICOLON]");
+}
+
+#[test]
+fn unclosed_delimiter() {
+    fn unclosed(open: u32, expected_close: SyntaxKind) -> ParseError {
+        ParseError::UnclosedDelimiter { open: TextRange::at(open.into(), 1.into()), expected_close }
+    }
+
+    assert_eq!(Root::parse("[ 1 2").errors(), [unclosed(0, T![']'])]);
+    assert_eq!(Root::parse("{ a = 1;").errors(), [unclosed(0, T!['}'])]);
+    assert_eq!(Root::parse("f (a b").errors(), [unclosed(2, T![')'])]);
+
+    // The inner delimiters are closed virtually, so the outer one still matches
+    let parse = Root::parse("{ a = [ 1 (2 }");
+    let errors = parse.errors();
+    // The missing `;` isn't reported, as the binding is broken already
+    assert_eq!(errors, [unclosed(10, T![')']), unclosed(6, T![']'])]);
+    let set = ast::AttrSet::try_from(parse.tree().expr().unwrap()).unwrap();
+    assert!(set.r_curly_token().is_some());
+
+    let errors = Root::parse("( 1 ]").ok_all().unwrap_err();
+    assert_eq!(errors[0], unclosed(0, T![')']));
+    assert_eq!(errors[0].to_string(), "unclosed delimiter at 0..1, wanted TOKEN_R_PAREN");
+    assert!(matches!(errors[1], ParseError::UnexpectedExtra { .. }));
+}
+
+#[test]
+fn unclosed_interpolation() {
+    fn unclosed(open: u32) -> Vec<ParseError> {
+        vec![ParseError::UnclosedInterpolation { open: TextRange::at(open.into(), 2.into()) }]
+    }
+
+    assert_eq!(Root::parse("\"${a").errors(), unclosed(1));
+    assert_eq!(Root::parse("x: \"a ${b\" + 1").errors(), unclosed(6));
+    assert_eq!(Root::parse("''${a''").errors(), unclosed(2));
+    assert_eq!(Root::parse("./a/${b").errors(), unclosed(4));
+
+    // The interpolation ends at the closing quote, and parsing continues after it
+    let parse = Root::parse("\"${a\" + 1");
+    assert_eq!(parse.errors(), unclosed(1));
+    assert_eq!(parse.errors()[0].to_string(), "unclosed interpolation at 1..3");
+    let add = ast::BinOp::try_from(parse.tree().expr().unwrap()).unwrap();
+    assert_eq!(add.operator(), Some(ast::BinOpKind::Add));
+    assert_eq!(add.rhs().unwrap().syntax().text(), "1");
+    let str = ast::Str::try_from(add.lhs().unwrap()).unwrap();
+    assert_eq!(str.syntax().to_string(), "\"${a\"");
+    assert!(str.syntax().last_token().is_some_and(|t| t.kind() == SyntaxKind::TOKEN_STRING_END));
+
+    // Nested strings that are terminated on the same line stay in it
+    let parse = Root::parse("[ \"${ f \"a\" \" 2 ]");
+    assert_eq!(parse.errors(), unclosed(3));
+    let list = ast::List::try_from(parse.tree().expr().unwrap()).unwrap();
+    let items: Vec<_> = list.items().map(|item| item.syntax().to_string()).collect();
+    assert_eq!(items, ["\"${ f \"a\" \"", "2"]);
+
+    // Or at the end of the line, which ends the string too
+    let parse = Root::parse("{ x = \"${a;\n  y = 1; }");
+    assert_eq!(parse.errors(), unclosed(7));
+    let set = ast::AttrSet::try_from(parse.tree().expr().unwrap()).unwrap();
+    assert_eq!(set.bound_names().collect::<Vec<_>>(), ["x", "y"]);
+
+    // Errors within the interpolation are kept
+    let errors = Root::parse("\"${a ]\"").ok_all().unwrap_err();
+    assert!(errors.contains(&unclosed(1)[0]), "{:?}", errors);
+    assert!(errors.len() > 1);
+}
+
+#[test]
+fn nested_braces_in_interpolation() {
+    fn interpolated(s: &str) -> ast::Expr {
+        let str = parse_as::<ast::Str>(s);
+        match str.parts().collect::<Vec<_>>().as_slice() {
+            [ast::InterpolPart::Interpolation(interpol)] => interpol.expr().unwrap(),
+            parts => panic!("unexpected parts {:?}", parts),
+        }
+    }
+
+    let select = ast::Select::try_from(interpolated(r#""${ {a=1;}.a }""#)).unwrap();
+    assert_eq!(select.syntax().to_string(), "{a=1;}.a");
+    assert!(matches!(select.expr(), Some(ast::Expr::AttrSet(_))));
+    let select = ast::Select::try_from(interpolated("''${ { b = { }; }.b }''")).unwrap();
+    assert_eq!(select.syntax().to_string(), "{ b = { }; }.b");
+
+    let nested = ast::Str::try_from(interpolated(r#""${ "${x}" }""#)).unwrap();
+    assert_eq!(nested.syntax().to_string(), r#""${x}""#);
+    let parts: Vec<_> = nested.parts().collect();
+    assert!(matches!(&parts[..], [ast::InterpolPart::Interpolation(_)]));
+}
+
+#[test]
+fn empty_interpolation() {
+    let parse = Root::parse(r#""a${}b""#);
+    assert_eq!(
+        parse.errors(),
+        [ParseError::EmptyInterpolation { range: TextRange::new(2.into(), 5.into()) }]
+    );
+    assert_eq!(parse.errors()[0].to_string(), "empty interpolation at 2..5");
+    let str = ast::Str::try_from(parse.tree().expr().unwrap()).unwrap();
+    let parts = str.normalized_parts();
+    assert_eq!(parts.len(), 3);
+    assert!(matches!(&parts[0], ast::InterpolPart::Literal(a) if a == "a"));
+    assert!(matches!(
+        &parts[1],
+        ast::InterpolPart::Interpolation(interpol)
+            if matches!(interpol.expr(), Some(ast::Expr::Error(_)))
+    ));
+    assert!(matches!(&parts[2], ast::InterpolPart::Literal(b) if b == "b"));
+
+    for s in ["./a/${}", "{ ${ } = 1; }", "x.${}"] {
+        let errors = Root::parse(s).errors().to_vec();
+        assert!(matches!(errors[..], [ParseError::EmptyInterpolation { .. }]), "{}", s);
+    }
+
+    // Broken strings and paths still have parts
+    let parse = Root::parse("''a\n${in\n] b''");
+    let str = parse.syntax().descendants().find_map(ast::Str::cast).unwrap();
+    assert_eq!(str.parts().count(), 2);
+    let parse = Root::parse("./a\n${b}");
+    let path = parse.syntax().descendants().find_map(ast::Path::cast).unwrap();
+    assert_eq!(path.syntax().to_string(), "./a");
+}
+
+#[test]
+fn binding_recovery() {
+    fn parse(s: &str) -> (Vec<String>, usize) {
+        let parse = Root::parse(s);
+        let set = ast::AttrSet::try_from(parse.tree().expr().unwrap()).unwrap();
+        let values = set.attrpath_values().map(|value| value.syntax().to_string()).collect();
+        (values, parse.errors().len())
+    }
+
+    let (values, errors) = parse("{ a = ; b = 1; c = 2; }");
+    assert_eq!(values, ["a = ;", "b = 1;", "c = 2;"]);
+    assert_eq!(errors, 1);
+
+    let (values, errors) = parse("{ = 1; b.= 2; c = (3; d = 4 +; e = 5; }");
+    assert_eq!(values, ["= 1;", "b.= 2;", "c = (3;", "d = 4 +;", "e = 5;"]);
+    assert_eq!(errors, 4);
+
+    // Without a `;`, the next binding still ends a broken one
+    let (values, errors) = parse("{ a = 1 % b = 2; }");
+    assert_eq!(values, ["a = 1 %", "b = 2;"]);
+    assert_eq!(errors, 1);
+}
+
+#[test]
+fn unexpected_char() {
+    let parse = Root::parse("{ x = $; }");
+    assert_eq!(parse.errors(), [ParseError::UnexpectedChar { range: range(6, 7), ch: '$' }]);
+    assert_eq!(parse.errors()[0].to_string(), "unexpected character '$' at 6..7");
+
+    let parse = Root::parse("[ 1 % 2 ]");
+    assert_eq!(parse.errors(), [ParseError::UnexpectedChar { range: range(4, 5), ch: '%' }]);
+
+    // Unterminated strings and comments get their own errors instead
+    let parse = Root::parse("\"a");
+    assert_eq!(parse.errors(), [ParseError::UnterminatedString { range: range(1, 2) }]);
+    let parse = Root::parse("1 /* abc");
+    assert_eq!(parse.errors(), [ParseError::UnterminatedComment { range: range(2, 8) }]);
+    assert_eq!(parse.errors()[0].to_string(), "unterminated comment at 2..8");
+
+    // Extra input that's reported as unexpected characters already isn't reported again
+    let parse = Root::parse("1 $");
+    assert_eq!(parse.errors(), [ParseError::UnexpectedChar { range: range(2, 3), ch: '$' }]);
+    let parse = Root::parse("1 $ 2");
+    assert_eq!(
+        parse.errors(),
+        [
+            ParseError::UnexpectedChar { range: range(2, 3), ch: '$' },
+            ParseError::UnexpectedExtra { range: range(2, 5) }
+        ]
+    );
+}
+
+#[test]
+fn parse_bytes() {
+    let parse = Root::parse_bytes(b"\xEF\xBB\xBF{ a = 1; }").unwrap();
+    assert!(!parse.has_errors());
+    assert_eq!(parse.source(), "{ a = 1; }");
+    assert_eq!(parse.syntax().text_range(), TextRange::up_to(10.into()));
+    assert_eq!(Root::parse_bytes(b"[ 1 ]").unwrap().source(), "[ 1 ]");
+
+    let error = Root::parse_bytes(b"\xEF\xBB\xBF\"a\xFFb\"").err().unwrap();
+    assert_eq!(error, EncodingError { offset: 5 });
+    assert_eq!(error.to_string(), "invalid UTF-8 at byte 5");
+    assert_eq!(Root::parse_bytes(b"\"\xC3\"").err(), Some(EncodingError { offset: 1 }));
+}
+
+#[test]
+fn missing_in() {
+    let parse = Root::parse("let x = 1; x");
+    assert_eq!(parse.errors(), [ParseError::ExpectedIn { range: range(11, 12) }]);
+    assert_eq!(parse.errors()[0].to_string(), "expected `in` before the body of `let` at 11..12");
+    let let_in = ast::LetIn::try_from(parse.tree().expr().unwrap()).unwrap();
+    assert_eq!(let_in.attrpath_values().count(), 1);
+    assert_eq!(let_in.in_token(), None);
+    assert_eq!(let_in.body().unwrap().syntax().text(), "x");
+
+    let parse = Root::parse("[ (let x = 1; { y = x; }) ]");
+    assert_eq!(parse.errors(), [ParseError::ExpectedIn { range: range(14, 15) }]);
+
+    // Broken bindings followed by an `in` aren't mistaken for the body
+    let parse = Root::parse("let x 1; in x");
+    assert!(matches!(parse.errors(), [ParseError::Unexpected { .. }]));
+}
+
+#[test]
+fn parse_cast() {
+    let parse = Root::parse("{ a = 1; }");
+    let green = parse.green().clone();
+    let expr = parse.clone().cast::<ast::Expr>().unwrap();
+    assert!(matches!(expr.tree(), ast::Expr::Root(_)));
+    assert_eq!(*expr.green(), green);
+    assert!(parse.cast::<ast::AttrSet>().is_none());
+
+    let parse = crate::parse_expr("{ a = 1; } 2");
+    assert!(parse.clone().cast::<ast::Apply>().is_some());
+    assert!(parse.cast::<ast::AttrSet>().is_none());
+
+    let parse = crate::parse_expr("{ a = ; }").cast::<ast::AttrSet>().unwrap();
+    assert_eq!(parse.errors().len(), 1);
+    assert_eq!(parse.tree().attrpath_values().count(), 1);
+}
+
+#[test]
+fn from_parts() {
+    let parse = Root::parse("{ a = ; }");
+    let (green, errors) = (parse.green().clone(), parse.errors().to_vec());
+    let restored = crate::Parse::<Root>::from_parts(green.clone(), errors);
+    assert_eq!(*restored.green(), green);
+    assert_eq!(restored.errors(), parse.errors());
+    assert_eq!(restored.debug_tree(), parse.debug_tree());
+    assert!(matches!(restored.tree().expr(), Some(ast::Expr::AttrSet(_))));
+
+    let restored = crate::Parse::<ast::Expr>::from_parts(green, Vec::new());
+    assert!(restored.cast::<ast::AttrSet>().is_none());
+
+    // A `parse_expr` result keeps its expression as the tree
+    let parse = crate::parse_expr(" 1 + 2 ");
+    let (green, errors) = (parse.green().clone(), parse.errors().to_vec());
+    let restored = crate::Parse::<ast::Expr>::from_expr_parts(green, errors);
+    assert_eq!(restored.tree().syntax().kind(), SyntaxKind::NODE_BIN_OP);
+    assert_eq!(restored.tree().syntax().text(), "1 + 2");
+    assert_eq!(restored.debug_tree(), parse.debug_tree());
+    assert!(restored.cast::<ast::BinOp>().is_some());
+}
+
+#[test]
+fn drop_errors() {
+    let parse = Root::parse("{ a = ; b = 1; }");
+    assert_eq!(parse.errors().len(), 1);
+    let green = parse.green().clone();
+    let parse = parse.drop_errors();
+    assert!(parse.errors().is_empty());
+    assert!(!parse.has_errors());
+    assert_eq!(*parse.green(), green);
+    assert_eq!(parse.source(), "{ a = ; b = 1; }");
+    assert!(parse.ok().is_ok());
+}
+
+#[test]
+fn parse_line_index() {
+    let parse = Root::parse("{\n  a = 1;\r\n  b = \"é\";\n}");
+    let index = parse.line_index();
+    assert_eq!(index.line_col(0.into()), (0, 0));
+    assert_eq!(index.line_col(4.into()), (1, 2));
+    assert_eq!(index.line_col(21.into()), (2, 9));
+    assert_eq!(index.line_col(24.into()), (3, 0));
+
+    // Built only once and shared with clones
+    let clone = parse.clone();
+    assert!(std::ptr::eq(clone.line_index(), index));
+    assert!(std::ptr::eq(clone.cast::<ast::Expr>().unwrap().line_index(), index));
+
+    let parse = Root::parse("{\n  a = ; }");
+    let range = parse.error_ranges()[0];
+    assert_eq!(parse.line_index().line_col(range.start()), (1, 6));
+}
+
+#[test]
+fn error_ranges() {
+    let parse = Root::parse("{ a = 1; }");
+    assert!(!parse.has_errors());
+    assert!(parse.error_ranges().is_empty());
+
+    let parse = Root::parse("{ a = ; b = ");
+    assert!(parse.has_errors());
+    assert_eq!(parse.error_ranges(), [range(6, 7), range(12, 12), range(0, 1)]);
+}
+
+#[test]
+fn comma_separated_bindings() {
+    let parse = Root::parse("{ a = 1, b = 2; }");
+    assert_eq!(parse.errors(), [ParseError::ExpectedSemicolon { found_comma: range(7, 8) }]);
+    assert_eq!(
+        parse.errors()[0].to_string(),
+        "unexpected `,` at 7..8, bindings are separated by `;` instead"
+    );
+    let set = ast::AttrSet::try_from(parse.tree().expr().unwrap()).unwrap();
+    let bindings: Vec<_> = set.attrpath_values().map(|it| it.syntax().to_string()).collect();
+    assert_eq!(bindings, ["a = 1,", "b = 2;"]);
+
+    let parse = Root::parse("let a = 1, b = a, in b");
+    assert_eq!(
+        parse.errors(),
+        [
+            ParseError::ExpectedSemicolon { found_comma: range(9, 10) },
+            ParseError::ExpectedSemicolon { found_comma: range(16, 17) }
+        ]
+    );
+    let let_in = ast::LetIn::try_from(parse.tree().expr().unwrap()).unwrap();
+    assert_eq!(let_in.attrpath_values().count(), 2);
+    assert_eq!(let_in.body().unwrap().syntax().to_string(), "b");
+}
+
+#[test]
+fn depth_limit() {
+    for input in ["(", "[", "!", "a ++ ", "{ a = "].map(|s| s.repeat(100_000)) {
+        let parse = Root::parse(&input);
+        assert!(parse.errors().contains(&ParseError::DepthLimitExceeded));
+        assert_eq!(parse.source(), input);
+    }
+
+    let parse = Root::parse_with_limit("[ [ [ 1 ] ] ]", 3);
+    assert_eq!(parse.errors()[0], ParseError::DepthLimitExceeded);
+    assert!(Root::parse_with_limit("[ [ [ 1 ] ] ]", 4).errors().is_empty());
+}
+
+#[test]
+fn legacy_let() {
+    let s = "let { a = 1; body = a; }";
+    let root = Root::parse_with_config(s, ParseConfig::default()).ok().unwrap();
+    let legacy_let = ast::LegacyLet::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(legacy_let.entries().count(), 2);
+    assert_eq!(legacy_let.body().unwrap().syntax().text(), "a");
+
+    let config = ParseConfig { allow_legacy_let: false, ..Default::default() };
+    let parse = Root::parse_with_config(s, config);
+    assert_eq!(
+        parse.errors(),
+        [ParseError::LegacyLet { range: TextRange::up_to(TextSize::of(s)) }]
+    );
+    assert!(ast::LegacyLet::cast(parse.tree().expr().unwrap().syntax().clone()).is_some());
+    assert!(Root::parse_with_config("let a = 1; in a", config).ok().is_ok());
+}
+
+#[test]
+fn fail_fast() {
+    let s = "{ a = ; b = [ 1 c = ; } # end";
+    assert!(Root::parse(s).errors().len() > 1);
+
+    let config = ParseConfig { recover: false, ..Default::default() };
+    let parse = Root::parse_with_config(s, config);
+    assert_eq!(parse.errors(), &Root::parse(s).errors()[..1]);
+    assert_eq!(parse.source(), s);
+    let last = parse.syntax().last_child().unwrap();
+    assert_eq!(last.kind(), SyntaxKind::NODE_ERROR);
+    assert_eq!(last.text(), "; b = [ 1 c = ; } # end");
+
+    assert!(Root::parse_with_config("{ a = 1; }", config).ok().is_ok());
+}
+
+#[test]
+fn or_as_keyword() {
+    let root = Root::parse("a.b or c").ok().unwrap();
+    let select = ast::Select::try_from(root.expr().unwrap()).unwrap();
+    assert_eq!(select.default_expr().unwrap().syntax().text(), "c");
+
+    let config = ParseConfig { or_as_keyword: false, ..Default::default() };
+    let root = Root::parse_with_config("a.b or c", config).ok().unwrap();
+    let (lambda, args) = ast::Apply::try_from(root.expr().unwrap()).unwrap().flatten();
+    assert_eq!(lambda.syntax().text(), "a.b");
+    let args: Vec<_> = args.iter().map(|arg| arg.syntax().to_string()).collect();
+    assert_eq!(args, ["or", "c"]);
+
+    // Without the keyword, `or` doesn't need anything in front of it
+    let s = "let or = x: x; in or a.or";
+    assert!(Root::parse_with_config(s, config).ok().is_ok());
+    assert!(Root::parse(s).ok().is_err());
+}
+
+#[test]
+fn parse_expr() {
+    let parse = crate::parse_expr("1 + 2");
+    assert!(parse.errors().is_empty());
+    assert!(matches!(parse.tree(), ast::Expr::BinOp(_)));
+
+    let parse = crate::parse_expr(" { a = 1; } # comment");
+    assert!(parse.errors().is_empty());
+    assert_eq!(parse.source(), " { a = 1; } # comment");
+    let ast::Expr::AttrSet(set) = parse.tree() else { panic!("not an attrset") };
+    assert_eq!(set.bound_names().collect::<Vec<_>>(), ["a"]);
+
+    let parse = crate::parse_expr("1 + 2 ) 3");
+    assert_eq!(
+        parse.errors(),
+        [ParseError::UnexpectedExtra { range: TextRange::new(6.into(), 9.into()) }]
+    );
+    assert!(matches!(parse.tree(), ast::Expr::Error(_)));
+    assert_eq!(parse.source(), "1 + 2 ) 3");
+
+    // Trivia around the expression stays outside of it
+    let parse = crate::parse_expr(" /abs/path ");
+    assert_eq!(parse.syntax().kind(), SyntaxKind::NODE_ROOT);
+    let ast::Expr::Path(path) = parse.tree() else { panic!("not a path") };
+    assert_eq!(path.kind(), ast::PathKind::Absolute);
+    assert_eq!(path.syntax().text(), "/abs/path");
+
+    let parse = crate::parse_expr("# The answer\n42");
+    assert_eq!(parse.tree().syntax().text(), "42");
+    assert_eq!(ast::doc_comment(parse.tree().syntax()).as_deref(), Some("The answer"));
+
+    // Input without an expression is all in an error node
+    for s in ["", " ", "\n ", "# c\n", "/* c */"] {
+        let parse = crate::parse_expr(s);
+        assert!(matches!(parse.tree(), ast::Expr::Error(_)), "{:?}", s);
+        assert_eq!(parse.tree().syntax().text(), s);
+        assert!(!parse.errors().is_empty(), "{:?}", s);
+    }
+}
+
+#[test]
+fn structural_eq() {
+    fn eq(a: &str, b: &str) -> bool {
+        crate::structural_eq(&Root::parse(a).syntax(), &Root::parse(b).syntax())
+    }
+
+    assert!(eq("{ a = 1; b = [ 1 2 ]; }", "{\n  a=1;\n  b = [\n    1\n    2\n  ];\n}\n"));
+    assert!(eq("f a # comment\n", "/* doc */ f\ta"));
+    assert!(eq("{ a = 1; }", &format::format(&Root::parse("{a=1;}").syntax())));
+
+    assert!(!eq("{ a = 1; }", "{ a = 2; }"));
+    assert!(!eq("a b", "ab"));
+    assert!(!eq(r#""a b""#, r#""a  b""#));
+    assert!(!eq("(a)", "a"));
+    assert!(!eq("a", "a b"));
+}
+
+#[test]
+fn structural_hash() {
+    fn hash(s: &str) -> u64 {
+        crate::structural_hash(&Root::parse(s).syntax())
+    }
+
+    assert_eq!(hash("{ a = 1; b = [ 1 2 ]; }"), hash("{\n  a=1;\n  b = [ 1 # one\n 2 ];\n}\n"));
+    assert_ne!(hash("{ a = 1; }"), hash("{ a = 2; }"));
+    assert_ne!(hash("a b"), hash("ab"));
+    assert_ne!(hash("(a)"), hash("a"));
+
+    // The same subtree at different offsets and depths
+    let root = Root::parse("[ { x = 1; } (f { x = 1; }) {x=1;} { x = 2; } ]").syntax();
+    let sets: Vec<_> =
+        root.descendants().filter(|node| node.kind() == SyntaxKind::NODE_ATTR_SET).collect();
+    let hashes: Vec<_> = sets.iter().map(crate::structural_hash).collect();
+    assert_eq!(hashes[0], hashes[1]);
+    assert_eq!(hashes[0], hashes[2]);
+    assert_ne!(hashes[0], hashes[3]);
+    for a in &sets {
+        for b in &sets {
+            assert_eq!(
+                crate::structural_eq(a, b),
+                crate::structural_hash(a) == crate::structural_hash(b)
+            );
+        }
+    }
+}
+
+#[test]
+fn green() {
+    let parse = Root::parse("{ a = 1; }");
+    let clone = parse.clone();
+    assert_eq!(parse.green(), clone.green());
+
+    let root = SyntaxNode::new_root(parse.green().clone());
+    assert_eq!(root.to_string(), "{ a = 1; }");
+    assert_eq!(root.green(), parse.syntax().green());
+}
+
+#[test]
+fn shared_cache() {
+    fn select(parse: &crate::Parse<Root>) -> GreenNode {
+        let node = parse
+            .syntax()
+            .descendants()
+            .find(|node| node.kind() == SyntaxKind::NODE_SELECT)
+            .unwrap();
+        node.green().into_owned()
+    }
+
+    let mut cache = NodeCache::default();
+    let a =
+        Root::parse_with_cache("{ lib, ... }: lib.mkOption { }", Default::default(), &mut cache);
+    let config = ParseConfig::default().with_or_as_keyword(false);
+    let b = Root::parse_with_cache("{ lib }: [ lib.mkOption ]", config, &mut cache);
+    assert_eq!(b.config(), config);
+    assert_eq!(a.syntax().to_string(), "{ lib, ... }: lib.mkOption { }");
+    assert!(std::ptr::eq::<rowan::GreenNodeData>(&*select(&a), &*select(&b)));
+
+    let c = Root::parse("lib.mkOption");
+    assert_eq!(select(&c), select(&a));
+    assert!(!std::ptr::eq::<rowan::GreenNodeData>(&*select(&c), &*select(&a)));
+
+    // The config is used, and kept for reparsing
+    let parse = Root::parse_with_cache("let or = 1; in or", config, &mut cache);
+    assert!(!parse.has_errors());
+    let edit =
+        crate::TextEdit { range: TextRange::at(9.into(), 1.into()), replacement: "2".into() };
+    let reparsed = parse.reparse(&edit);
+    assert_eq!(reparsed.source(), "let or = 2; in or");
+    assert_eq!(reparsed.config(), config);
+    assert!(!reparsed.has_errors());
+}
+
+#[test]
+fn debug_tree() {
+    expect![[r#"
+        NODE_ROOT@0..14
+          NODE_ATTR_SET@0..14
+            TOKEN_L_BRACE@0..1 "{"
+            TOKEN_WHITESPACE@1..2 " "
+            NODE_ATTRPATH_VALUE@2..8
+              NODE_ATTRPATH@2..3
+                NODE_IDENT@2..3
+                  TOKEN_IDENT@2..3 "a"
+              TOKEN_WHITESPACE@3..4 " "
+              TOKEN_ASSIGN@4..5 "="
+              TOKEN_WHITESPACE@5..6 " "
+              NODE_LITERAL@6..7
+                TOKEN_INTEGER@6..7 "1"
+              TOKEN_SEMICOLON@7..8 ";"
+            TOKEN_WHITESPACE@8..9 " "
+            NODE_ATTRPATH_VALUE@9..13
+              NODE_ATTRPATH@9..10
+                NODE_IDENT@9..10
+                  TOKEN_IDENT@9..10 "b"
+              TOKEN_WHITESPACE@10..11 " "
+              TOKEN_ASSIGN@11..12 "="
+              TOKEN_WHITESPACE@12..13 " "
+            TOKEN_R_BRACE@13..14 "}"
+        error: unexpected TOKEN_R_BRACE at 13..14, wanted any of [TOKEN_L_PAREN, TOKEN_REC, TOKEN_L_BRACE, TOKEN_L_BRACK, TOKEN_STRING_START, TOKEN_IDENT]
+    "#]].assert_eq(&Root::parse("{ a = 1; b = }").debug_tree());
+}
+
+#[test]
+fn string_unterminated() {
+    let parse = Root::parse(include_str!("../test_data/parser/error/string_unterminated.nix"));
+    assert!(matches!(parse.errors()[0], ParseError::UnterminatedString { .. }));
+
+    let set = ast::AttrSet::try_from(parse.tree().expr().unwrap()).unwrap();
+    let names: Vec<String> = set.bound_names().collect();
+    assert_eq!(names, ["x", "y"]);
+}
+
+#[test]
+fn binding_power() {
+    let (update_l, update_r) = parser::binding_power(T!["//"]).unwrap();
+    assert!(update_l > update_r);
+    assert_eq!(parser::operator_assoc(ast::BinOpKind::Update), parser::Assoc::Right);
+
+    for op in [T![<], T![<=], T![>], T![>=], T![==], T![!=]] {
+        let (l, r) = parser::binding_power(op).unwrap();
+        assert!(update_r > l.max(r));
+    }
+
+    assert_eq!(parser::binding_power(T![=]), None);
+}
+
+#[test]
+fn binding_power_matches_parser() {
+    use ast::BinOpKind::*;
+    use parser::Assoc;
+
+    // Every binary operator and `?`, with its token text and associativity
+    let mut ops: Vec<_> = [
+        PipeLeft,
+        PipeRight,
+        Implication,
+        Or,
+        And,
+        Equal,
+        NotEqual,
+        Less,
+        LessOrEq,
+        More,
+        MoreOrEq,
+        Update,
+        Add,
+        Sub,
+        Mul,
+        Div,
+        Concat,
+    ]
+    .into_iter()
+    .map(|op| (op.token(), op.as_str(), parser::operator_assoc(op)))
+    .collect();
+    ops.push((T![?], "?", Assoc::Left));
+
+    let is_op = |node: &SyntaxNode| {
+        matches!(node.kind(), SyntaxKind::NODE_BIN_OP | SyntaxKind::NODE_HAS_ATTR)
+    };
+    for &(op1, text1, assoc1) in &ops {
+        let (l1, r1) = parser::binding_power(op1).unwrap();
+        match assoc1 {
+            Assoc::Right => assert!(l1 > r1, "{:?}", op1),
+            Assoc::Left | Assoc::None => assert!(l1 < r1, "{:?}", op1),
+        }
+        for &(op2, text2, _) in &ops {
+            let (l2, r2) = parser::binding_power(op2).unwrap();
+            let code = format!("a {} b {} c", text1, text2);
+            let parse = Root::parse(&code);
+            if assoc1 == Assoc::None && (l1, r1) == (l2, r2) {
+                assert!(!parse.errors().is_empty(), "{}", code);
+                continue;
+            }
+            assert_eq!(parse.errors(), [], "{}", code);
+
+            // `op2` takes `b` from `op1` if it binds tighter
+            let top = parse.tree().expr().unwrap().syntax().clone();
+            let (lhs, rhs) = (top.first_child().unwrap(), top.last_child().unwrap());
+            if l2 > r1 {
+                assert!(lhs.to_string() == "a" && is_op(&rhs), "{}", code);
+            } else {
+                assert!(is_op(&lhs) && rhs.to_string() == "c", "{}", code);
+            }
+        }
+    }
+}
+
 #[test]
 fn t_macro() {
     assert_eq!(T![@], SyntaxKind::TOKEN_AT);
     assert!(matches!(SyntaxKind::TOKEN_L_PAREN, T!['(']));
 }
 
+#[test]
+fn round_trip() {
+    fn check_dir(path: &Path) {
+        for entry in path.read_dir().unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                check_dir(&path);
+            } else if path.extension() == Some(OsStr::new("nix")) {
+                let code = fs::read_to_string(&path).unwrap();
+                // Every prefix, to also cover all kinds of incomplete input
+                for (i, _) in code.char_indices().chain(Some((code.len(), ' '))) {
+                    let code = &code[..i];
+                    assert_eq!(Root::parse(code).source(), code, "{}", path.display());
+                }
+            }
+        }
+    }
+
+    check_dir(&[env!("CARGO_MANIFEST_DIR"), "test_data"].iter().collect::<PathBuf>());
+}
+
 fn dir_tests<F>(dir: &str, get_actual: F)
 where
     F: Fn(String) -> String,
@@ -115,6 +1291,137 @@ fn parser_dir_tests() {
     })
 }
 
+#[test]
+fn format_dir_tests() {
+    fn tokens(code: &str) -> Vec<(SyntaxKind, String)> {
+        tokenize(code)
+            .into_iter()
+            .filter(|(kind, _)| *kind != SyntaxKind::TOKEN_WHITESPACE)
+            .map(|(kind, str)| (kind, str.to_string()))
+            .collect()
+    }
+
+    dir_tests("format", |code| {
+        let actual = format::format(&Root::parse(&code).syntax());
+        assert_eq!(tokens(&actual), tokens(&code), "formatting changed more than whitespace");
+        assert_eq!(format::format(&Root::parse(&actual).syntax()), actual, "not idempotent");
+        actual
+    })
+}
+
+#[test]
+fn tokens() {
+    let input = include_str!("../test_data/parser/success/interpolation.nix");
+    let tokens: Vec<_> = crate::tokens(input).collect();
+    assert_eq!(tokens, tokenize(input));
+    for (_, text) in tokens {
+        assert!(input.as_bytes().as_ptr_range().contains(&text.as_ptr()));
+    }
+}
+
+#[test]
+fn search_paths() {
+    use SyntaxKind::*;
+
+    assert_eq!(tokenize("x<y"), [(TOKEN_IDENT, "x"), (TOKEN_LESS, "<"), (TOKEN_IDENT, "y")]);
+    assert_eq!(
+        tokenize("x < y"),
+        [
+            (TOKEN_IDENT, "x"),
+            (TOKEN_WHITESPACE, " "),
+            (TOKEN_LESS, "<"),
+            (TOKEN_WHITESPACE, " "),
+            (TOKEN_IDENT, "y"),
+        ]
+    );
+    assert_eq!(tokenize("<nixpkgs>"), [(TOKEN_SPATH, "<nixpkgs>")]);
+    assert_eq!(tokenize("<nixpkgs/lib>"), [(TOKEN_SPATH, "<nixpkgs/lib>")]);
+    // Like in Nix, a `<` followed by a valid search path is one
+    assert_eq!(tokenize("a<b>c"), [(TOKEN_IDENT, "a"), (TOKEN_SPATH, "<b>"), (TOKEN_IDENT, "c")]);
+    assert_eq!(tokenize("1<2"), [(TOKEN_INTEGER, "1"), (TOKEN_LESS, "<"), (TOKEN_INTEGER, "2")]);
+
+    let path = ast::Path::try_from(Root::parse("<nixpkgs/lib>").tree().expr().unwrap()).unwrap();
+    assert_eq!(path.kind(), ast::PathKind::Search);
+    assert_eq!(path.syntax().first_token().unwrap().kind(), TOKEN_SPATH);
+}
+
+#[test]
+fn float_after_select() {
+    // Like Nix, the longest match wins, so these apply `a` to the float `.5`
+    for s in ["a.5", "(a).5"] {
+        let apply = parse_as::<ast::Apply>(s);
+        assert_eq!(apply.argument().unwrap().syntax().text(), ".5");
+    }
+    // and this is a list of `a` and `.5`
+    let root = Root::parse("[ a.5 ]").ok().unwrap();
+    let list = ast::List::try_from(root.expr().unwrap()).unwrap();
+    let items: Vec<_> = list.items().map(|item| item.syntax().to_string()).collect();
+    assert_eq!(items, ["a", ".5"]);
+}
+
+#[test]
+fn tokenizer_ranges() {
+    let input = "let\n  a = \"b ${c}\"; # d\nin [ a ./e/${a} ]";
+    let kinds: Vec<_> =
+        Tokenizer::new(input).with_ranges().map(|(kind, range)| (kind, &input[range])).collect();
+    assert_eq!(kinds, tokenize(input));
+
+    // Stopping and resuming where it left off
+    let mut tokenizer = Tokenizer::new(input);
+    assert_eq!(tokenizer.next(), Some((SyntaxKind::TOKEN_LET, "let")));
+    assert_eq!(tokenizer.offset(), 3.into());
+    let rest: Vec<_> = tokenizer.by_ref().collect();
+    assert_eq!(rest, tokenize(input)[1..]);
+    assert_eq!(tokenizer.next(), None);
+    assert_eq!(tokenizer.offset(), (input.len() as u32).into());
+}
+
+#[test]
+fn ascii_fast_path() {
+    // A non-ASCII comment in front makes the tokenizer take the slower path
+    // that works on chars, which must give the same tokens as the bytewise one
+    let base_path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "test_data"].iter().collect();
+    for dir in ["parser", "tokenizer"] {
+        for kind in ["success", "error"] {
+            for entry in base_path.join(dir).join(kind).read_dir().unwrap() {
+                let path = entry.unwrap().path();
+                if path.extension() != Some(OsStr::new("nix")) {
+                    continue;
+                }
+                let code = fs::read_to_string(&path).unwrap();
+                if !code.is_ascii() {
+                    continue;
+                }
+                let prefixed = format!("/* é */{}", code);
+                let tokens = tokenize(&prefixed);
+                assert_eq!(tokens[0], (SyntaxKind::TOKEN_COMMENT, "/* é */"));
+                assert_eq!(tokens[1..], tokenize(&code), "{}", path.display());
+            }
+        }
+    }
+}
+
+#[test]
+fn crlf_comments() {
+    use SyntaxKind::*;
+
+    let tokens = tokenize("# a\r\n[ # b\r\n]");
+    assert_eq!(
+        tokens,
+        [
+            (TOKEN_COMMENT, "# a"),
+            (TOKEN_WHITESPACE, "\r\n"),
+            (TOKEN_L_BRACK, "["),
+            (TOKEN_WHITESPACE, " "),
+            (TOKEN_COMMENT, "# b"),
+            (TOKEN_WHITESPACE, "\r\n"),
+            (TOKEN_R_BRACK, "]"),
+        ]
+    );
+    // A lone `\r` doesn't end a comment
+    assert_eq!(tokenize("# a\rb"), [(TOKEN_COMMENT, "# a\rb")]);
+}
+
 #[test]
 fn tokenizer_dir_tests() {
     dir_tests("tokenizer", |code| {
@@ -1,6 +1,9 @@
 //! The tokenizer: turns a string into tokens, such as numbers, strings, and keywords
 
-use crate::SyntaxKind::{self, *};
+use crate::{
+    SyntaxKind::{self, *},
+    TextRange, TextSize,
+};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum IdentType {
@@ -51,15 +54,63 @@ pub fn tokenize(input: &str) -> Vec<Token<'_>> {
     Tokenizer::new(input).collect()
 }
 
+/// Lazily tokenize the given input. Like `tokenize`, the text of every token
+/// borrows from `input`, but no vector of all tokens is allocated.
+pub fn tokens(input: &str) -> impl Iterator<Item = Token<'_>> {
+    Tokenizer::new(input)
+}
+
 /// The tokenizer. You may want to use the `tokenize` convenience function from this module instead.
+///
+/// As an `Iterator`, it yields every token of the input with its kind and
+/// text, including whitespace and comments, so that the texts of all tokens
+/// add up to the input. Input that isn't valid Nix is yielded as
+/// `TOKEN_ERROR` tokens. Tokenizing never panics and ends at the end of the
+/// input, after which `next` keeps returning `None`. Tokens are produced one
+/// at a time, so tokenizing can be stopped at any point and resumed later.
 pub struct Tokenizer<'a> {
     ctx: Vec<Context>,
     state: State<'a>,
+    /// Whether the input is all ASCII, so that it can be scanned byte by byte
+    /// instead of decoding characters
+    ascii: bool,
+    /// The offsets at which to end an interpolation in a string, see
+    /// `with_interpol_ends`
+    interpol_ends: Vec<usize>,
 }
 
 impl<'a> Tokenizer<'a> {
     pub fn new(input: &'a str) -> Self {
-        Self { ctx: Vec::new(), state: State { input, offset: 0 } }
+        Self {
+            ctx: Vec::new(),
+            state: State { input, offset: 0 },
+            ascii: input.is_ascii(),
+            interpol_ends: Vec::new(),
+        }
+    }
+
+    /// End the interpolations in strings that are still open at one of
+    /// `offsets`, as if they were closed right before. If the closing quote of
+    /// the string is there, it ends the string, and so does a newline in a
+    /// double-quoted string, which is lexed as code instead. This is how the
+    /// parser recovers from a `${` without `}`.
+    pub(crate) fn with_interpol_ends(mut self, offsets: Vec<usize>) -> Self {
+        self.interpol_ends = offsets;
+        self
+    }
+
+    /// The offset in the input the next token starts at
+    pub fn offset(&self) -> TextSize {
+        TextSize::from(self.state.offset as u32)
+    }
+
+    /// Yield the range of each token in the input instead of its text
+    pub fn with_ranges(mut self) -> impl Iterator<Item = (SyntaxKind, TextRange)> + 'a {
+        std::iter::from_fn(move || {
+            let start = self.offset();
+            let (kind, _) = Iterator::next(&mut self)?;
+            Some((kind, TextRange::new(start, self.offset())))
+        })
     }
 }
 
@@ -68,7 +119,11 @@ impl Tokenizer<'_> {
         &self.state.input[self.state.offset..]
     }
     fn peek(&self) -> Option<char> {
-        self.remaining().chars().next()
+        if self.ascii {
+            self.remaining().as_bytes().first().map(|&b| char::from(b))
+        } else {
+            self.remaining().chars().next()
+        }
     }
     fn next(&mut self) -> Option<char> {
         let c = self.peek();
@@ -77,6 +132,10 @@ impl Tokenizer<'_> {
         }
         c
     }
+    fn peek_after_bump(&mut self) -> Option<char> {
+        self.next();
+        self.peek()
+    }
     fn starts_with_bump(&mut self, s: &str) -> bool {
         let starts_with = self.remaining().starts_with(s);
         if starts_with {
@@ -97,19 +156,33 @@ impl Tokenizer<'_> {
         self.ctx.pop();
     }
 
-    fn consume<F>(&mut self, mut f: F) -> usize
+    /// The length in bytes of the remaining input before the first character
+    /// not matching `f`
+    fn len_while<F>(&self, mut f: F) -> usize
     where
         F: FnMut(char) -> bool,
     {
-        let len: usize =
-            self.remaining().chars().take_while(|&c| f(c)).map(|c| c.len_utf8()).sum::<usize>();
+        if self.ascii {
+            self.remaining().bytes().take_while(|&b| f(char::from(b))).count()
+        } else {
+            self.remaining().chars().take_while(|&c| f(c)).map(char::len_utf8).sum()
+        }
+    }
+
+    fn consume<F>(&mut self, f: F) -> usize
+    where
+        F: FnMut(char) -> bool,
+    {
+        let len = self.len_while(f);
         self.state.offset += len;
         len
     }
     fn next_string(&mut self, multiline: bool) -> SyntaxKind {
+        let token_start = self.state;
         loop {
             let start = self.state;
             match self.next() {
+                None if !multiline => return self.unterminated_string(token_start),
                 None => {
                     self.pop_ctx(Context::StringBody { multiline });
                     return TOKEN_ERROR;
@@ -120,23 +193,23 @@ impl Tokenizer<'_> {
                     self.push_ctx(Context::StringEnd);
                     return TOKEN_STRING_CONTENT;
                 }
-                Some('\\') if !multiline => match self.next() {
-                    None => return TOKEN_ERROR,
-                    Some(_) => (),
-                },
+                // Escapes like `\${` and `''${` are kept in the content, which
+                // stops the `${` after them from starting an interpolation
+                Some('\\') if !multiline => {
+                    if self.next().is_none() {
+                        return self.unterminated_string(token_start);
+                    }
+                }
 
                 Some('\'') if multiline => match self.peek() {
                     None => return TOKEN_ERROR,
-                    Some('\'') => match {
-                        self.next();
-                        self.peek()
-                    } {
+                    Some('\'') => match self.peek_after_bump() {
                         Some('\'') | Some('$') => {
                             self.next().unwrap();
                         }
                         Some('\\') => {
                             self.next().unwrap();
-                            if let None = self.next() {
+                            if self.next().is_none() {
                                 return TOKEN_ERROR;
                             }
                         }
@@ -166,6 +239,16 @@ impl Tokenizer<'_> {
         }
     }
 
+    /// End an unterminated double-quoted string after the first newline of its
+    /// remaining content, so that it doesn't swallow the rest of the file.
+    fn unterminated_string(&mut self, token_start: State) -> SyntaxKind {
+        if let Some(newline) = self.str_since(token_start).find('\n') {
+            self.state.offset = token_start.offset + newline + 1;
+        }
+        self.pop_ctx(Context::StringBody { multiline: false });
+        TOKEN_ERROR
+    }
+
     fn check_path_since(&mut self, past: State) -> SyntaxKind {
         self.consume(is_valid_path_char);
         if self.remaining().starts_with("${") {
@@ -181,6 +264,18 @@ impl Tokenizer<'_> {
     fn next_inner(&mut self) -> Option<SyntaxKind> {
         let start = self.state;
 
+        if let [.., Context::StringBody { multiline }, Context::Interpol { .. }] = self.ctx[..] {
+            if self.interpol_ends.contains(&self.state.offset) {
+                let at_close = self.remaining().starts_with(if multiline { "''" } else { "\"" });
+                if at_close || !multiline {
+                    self.ctx.truncate(self.ctx.len() - 2);
+                }
+                if at_close {
+                    self.push_ctx(Context::StringEnd);
+                }
+            }
+        }
+
         // Handle already started multi-token
         loop {
             match self.ctx.last() {
@@ -197,7 +292,7 @@ impl Tokenizer<'_> {
                     if self.starts_with_bump("${") {
                         self.ctx.push(Context::Interpol { brackets: 0 });
                         return Some(TOKEN_INTERPOL_START);
-                    } else if self.peek().map_or(false, is_valid_path_char) {
+                    } else if self.peek().is_some_and(is_valid_path_char) {
                         return Some(self.check_path_since(start));
                     } else {
                         self.pop_ctx(Context::Path);
@@ -218,10 +313,7 @@ impl Tokenizer<'_> {
                             self.next().unwrap();
                             true
                         }
-                        Some('\'') => match {
-                            self.next().unwrap();
-                            self.peek()
-                        } {
+                        Some('\'') => match self.peek_after_bump() {
                             Some('\'') => {
                                 self.next().unwrap();
                                 true
@@ -243,8 +335,14 @@ impl Tokenizer<'_> {
 
         if self.peek() == Some('#') {
             self.consume(|c| c != '\n');
+            // The `\r` of a `\r\n` line ending is whitespace, not part of the comment
+            if self.remaining().starts_with('\n') && self.str_since(start).ends_with('\r') {
+                self.state.offset -= 1;
+            }
             return Some(TOKEN_COMMENT);
         }
+        // Block comments don't nest, the first `*/` ends them. Without one,
+        // everything up to the end is an error token.
         if self.starts_with_bump("/*") {
             loop {
                 self.consume(|c| c != '*');
@@ -267,16 +365,11 @@ impl Tokenizer<'_> {
         // Check if it's a path
         let store_path = self.peek() == Some('<');
         let kind = {
-            let skipped = self
-                .remaining()
-                .chars()
-                .take_while(|&c| match c {
-                    '<' | '/' => store_path,
-                    _ => is_valid_path_char(c),
-                })
-                .collect::<String>();
-
-            let mut lookahead = self.remaining().chars().skip(skipped.chars().count());
+            let (skipped, rest) = self.remaining().split_at(self.len_while(|c| match c {
+                '<' | '/' => store_path,
+                _ => is_valid_path_char(c),
+            }));
+            let mut lookahead = rest.chars();
 
             match (lookahead.next(), lookahead.next()) {
                 // a//b parses as Update(a, b)
@@ -336,8 +429,8 @@ impl Tokenizer<'_> {
             ':' => TOKEN_COLON,
             ',' => TOKEN_COMMA,
             '.' => {
-                if self.peek().map_or(false, |x| ('0'..='9').contains(&x)) {
-                    self.consume(|c| ('0'..='9').contains(&c));
+                if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.consume(|c| c.is_ascii_digit());
                     self.consume_scientific()
                 } else {
                     TOKEN_DOT
@@ -373,7 +466,7 @@ impl Tokenizer<'_> {
                 if self.next() != Some('>') {
                     TOKEN_ERROR
                 } else {
-                    TOKEN_PATH
+                    TOKEN_SPATH
                 }
             }
             '&' if self.peek() == Some('&') => {
@@ -444,10 +537,10 @@ impl Tokenizer<'_> {
                 TOKEN_STRING_START
             }
             '0'..='9' => {
-                self.consume(|c| ('0'..='9').contains(&c));
+                self.consume(|c| c.is_ascii_digit());
                 if self.peek() == Some('.') {
                     self.next().unwrap();
-                    self.consume(|c| ('0'..='9').contains(&c));
+                    self.consume(|c| c.is_ascii_digit());
                     self.consume_scientific()
                 } else {
                     TOKEN_INTEGER
@@ -463,7 +556,7 @@ impl Tokenizer<'_> {
             if self.peek() == Some('-') || self.peek() == Some('+') {
                 self.next().unwrap();
             }
-            if self.consume(|c| ('0'..='9').contains(&c)) == 0 {
+            if self.consume(|c| c.is_ascii_digit()) == 0 {
                 return TOKEN_ERROR;
             }
         }
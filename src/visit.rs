@@ -0,0 +1,358 @@
+//! Traversal of the typed AST with callbacks for every node type
+
+use std::collections::HashMap;
+
+use rowan::{ast::AstNode, Direction, TextSize, TokenAtOffset, WalkEvent};
+
+use crate::{ast, match_ast, NixLanguage, SyntaxKind, SyntaxNode, SyntaxToken};
+
+macro_rules! visitor {
+    ($($ty:ident => $visit:ident, $leave:ident;)*) => {
+        /// Callbacks for `walk`. For every node, `visit_*` is called before and
+        /// `leave_*` after its children are walked. All methods do nothing by
+        /// default.
+        pub trait Visitor {
+            $(
+                fn $visit(&mut self, node: &ast::$ty) {
+                    let _ = node;
+                }
+                fn $leave(&mut self, node: &ast::$ty) {
+                    let _ = node;
+                }
+            )*
+        }
+
+        fn enter(visitor: &mut impl Visitor, node: &SyntaxNode) {
+            match_ast! {
+                match node {
+                    $( ast::$ty(it) => visitor.$visit(&it), )*
+                    _ => (),
+                }
+            }
+        }
+
+        fn leave(visitor: &mut impl Visitor, node: &SyntaxNode) {
+            match_ast! {
+                match node {
+                    $( ast::$ty(it) => visitor.$leave(&it), )*
+                    _ => (),
+                }
+            }
+        }
+    };
+}
+
+visitor! {
+    Apply => visit_apply, leave_apply;
+    Assert => visit_assert, leave_assert;
+    Attrpath => visit_attrpath, leave_attrpath;
+    AttrpathValue => visit_attrpath_value, leave_attrpath_value;
+    AttrSet => visit_attr_set, leave_attr_set;
+    BinOp => visit_bin_op, leave_bin_op;
+    Dynamic => visit_dynamic, leave_dynamic;
+    Error => visit_error, leave_error;
+    HasAttr => visit_has_attr, leave_has_attr;
+    Ident => visit_ident, leave_ident;
+    IdentParam => visit_ident_param, leave_ident_param;
+    IfElse => visit_if_else, leave_if_else;
+    Inherit => visit_inherit, leave_inherit;
+    InheritFrom => visit_inherit_from, leave_inherit_from;
+    Interpol => visit_interpol, leave_interpol;
+    Lambda => visit_lambda, leave_lambda;
+    LegacyLet => visit_legacy_let, leave_legacy_let;
+    LetIn => visit_let_in, leave_let_in;
+    List => visit_list, leave_list;
+    Literal => visit_literal, leave_literal;
+    Paren => visit_paren, leave_paren;
+    PatBind => visit_pat_bind, leave_pat_bind;
+    PatEntry => visit_pat_entry, leave_pat_entry;
+    Path => visit_path, leave_path;
+    Pattern => visit_pattern, leave_pattern;
+    Root => visit_root, leave_root;
+    Select => visit_select, leave_select;
+    Str => visit_str, leave_str;
+    UnaryOp => visit_unary_op, leave_unary_op;
+    With => visit_with, leave_with;
+}
+
+/// Walk `root` and all of its descendants in preorder, calling the callbacks
+/// of `visitor` for each of them
+pub fn walk(visitor: &mut impl Visitor, root: &SyntaxNode) {
+    for event in root.preorder() {
+        match event {
+            WalkEvent::Enter(node) => enter(visitor, &node),
+            WalkEvent::Leave(node) => leave(visitor, &node),
+        }
+    }
+}
+
+/// The ancestors of `node` of type `N`, starting with `node` itself
+pub fn typed_ancestors<N: AstNode<Language = NixLanguage>>(
+    node: &SyntaxNode,
+) -> impl Iterator<Item = N> {
+    node.ancestors().filter_map(N::cast)
+}
+
+/// The descendants of `node` of type `N` in preorder, starting with `node`
+/// itself
+pub fn typed_descendants<N: AstNode<Language = NixLanguage>>(
+    node: &SyntaxNode,
+) -> impl Iterator<Item = N> {
+    node.descendants().filter_map(N::cast)
+}
+
+/// The siblings of `node` of type `N` in `direction`, starting with `node`
+/// itself
+pub fn typed_siblings<N: AstNode<Language = NixLanguage>>(
+    node: &SyntaxNode,
+    direction: Direction,
+) -> impl Iterator<Item = N> {
+    node.siblings(direction).filter_map(N::cast)
+}
+
+/// Entering and leaving the nodes of type `N` within `root` in preorder,
+/// starting with `root` itself, like `SyntaxNode::preorder` with all other
+/// nodes left out
+pub fn preorder_typed<N: AstNode<Language = NixLanguage>>(
+    root: &SyntaxNode,
+) -> impl Iterator<Item = WalkEvent<N>> {
+    root.preorder().filter_map(|event| match event {
+        WalkEvent::Enter(node) => N::cast(node).map(WalkEvent::Enter),
+        WalkEvent::Leave(node) => N::cast(node).map(WalkEvent::Leave),
+    })
+}
+
+/// The first token of `node` that isn't whitespace or a comment, unlike
+/// `SyntaxNode::first_token`. Returns `None` if `node` has only trivia.
+pub fn first_token_skip_trivia(node: &SyntaxNode) -> Option<SyntaxToken> {
+    std::iter::successors(node.first_token(), SyntaxToken::next_token)
+        .take_while(|token| token.parent_ancestors().any(|it| it == *node))
+        .find(|token| !token.kind().is_trivia())
+}
+
+/// The last token of `node` that isn't whitespace or a comment, unlike
+/// `SyntaxNode::last_token`. Returns `None` if `node` has only trivia.
+pub fn last_token_skip_trivia(node: &SyntaxNode) -> Option<SyntaxToken> {
+    std::iter::successors(node.last_token(), SyntaxToken::prev_token)
+        .take_while(|token| token.parent_ancestors().any(|it| it == *node))
+        .find(|token| !token.kind().is_trivia())
+}
+
+/// The tokens of `root` touching `offset`: the one it's in, or both if it's
+/// on the boundary between two, or none if it's outside of `root`
+pub fn token_at_offset(root: &SyntaxNode, offset: TextSize) -> TokenAtOffset<SyntaxToken> {
+    if root.text_range().contains_inclusive(offset) {
+        root.token_at_offset(offset)
+    } else {
+        TokenAtOffset::None
+    }
+}
+
+/// The innermost node containing the token at `offset`, see
+/// `token_at_offset`. On a boundary between tokens, the one that isn't trivia
+/// is picked, preferring the right one if neither is.
+pub fn node_at_offset(root: &SyntaxNode, offset: TextSize) -> Option<SyntaxNode> {
+    let token = match token_at_offset(root, offset) {
+        TokenAtOffset::None => return None,
+        TokenAtOffset::Single(token) => token,
+        TokenAtOffset::Between(left, right) => {
+            if right.kind().is_trivia() && !left.kind().is_trivia() {
+                left
+            } else {
+                right
+            }
+        }
+    };
+    token.parent()
+}
+
+/// The number of nodes and tokens of every kind within `root`, including
+/// `root` itself. Kinds that don't occur aren't in the map.
+pub fn kind_histogram(root: &SyntaxNode) -> HashMap<SyntaxKind, usize> {
+    let mut histogram = HashMap::new();
+    for element in root.descendants_with_tokens() {
+        *histogram.entry(element.kind()).or_insert(0) += 1;
+    }
+    histogram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::HasEntry, Root};
+
+    #[test]
+    fn count_applies() {
+        #[derive(Default)]
+        struct Applies(usize);
+
+        impl Visitor for Applies {
+            fn visit_apply(&mut self, _: &ast::Apply) {
+                self.0 += 1;
+            }
+        }
+
+        let mut applies = Applies::default();
+        walk(&mut applies, &Root::parse("f a (g b) [ (h (i j)) ]").syntax());
+        assert_eq!(applies.0, 6);
+    }
+
+    #[test]
+    fn scopes() {
+        #[derive(Default)]
+        struct Scopes {
+            stack: Vec<Vec<String>>,
+            seen: Vec<(String, usize)>,
+        }
+
+        impl Visitor for Scopes {
+            fn visit_let_in(&mut self, node: &ast::LetIn) {
+                self.stack.push(node.bound_names().collect());
+            }
+            fn leave_let_in(&mut self, _: &ast::LetIn) {
+                self.stack.pop();
+            }
+            fn visit_ident(&mut self, node: &ast::Ident) {
+                self.seen.push((node.to_string(), self.stack.len()));
+            }
+        }
+
+        let mut scopes = Scopes::default();
+        walk(&mut scopes, &Root::parse("f (let a = 1; in let b = a; in b) c").syntax());
+        assert!(scopes.stack.is_empty());
+        let seen: Vec<_> =
+            scopes.seen.iter().map(|(name, depth)| (name.as_str(), *depth)).collect();
+        assert_eq!(seen, [("f", 0), ("a", 1), ("b", 2), ("a", 2), ("b", 2), ("c", 0)]);
+    }
+
+    #[test]
+    fn typed() {
+        let root = Root::parse(r#"{ a = "x"; b = [ "y${"z"}" ./p ]; }"#).syntax();
+        let strings: Vec<_> =
+            typed_descendants::<ast::Str>(&root).map(|it| it.syntax().to_string()).collect();
+        assert_eq!(strings, [r#""x""#, r#""y${"z"}""#, r#""z""#]);
+
+        let inner = typed_descendants::<ast::Str>(&root).last().unwrap();
+        let outer: Vec<ast::Str> = typed_ancestors(inner.syntax()).collect();
+        assert_eq!(outer.len(), 2);
+        assert_eq!(typed_ancestors::<ast::AttrSet>(inner.syntax()).count(), 1);
+
+        let list = typed_descendants::<ast::List>(&root).next().unwrap();
+        let first = list.items().next().unwrap();
+        assert_eq!(typed_siblings::<ast::Path>(first.syntax(), Direction::Next).count(), 1);
+        assert_eq!(typed_siblings::<ast::Str>(first.syntax(), Direction::Next).count(), 1);
+    }
+
+    #[test]
+    fn preorder() {
+        let root = Root::parse("let a = [ (f 1) ]; in { b = a.c; }").syntax();
+        let mut depth = 0;
+        let mut entered = Vec::new();
+        for event in preorder_typed::<ast::Expr>(&root) {
+            match event {
+                WalkEvent::Enter(expr) => {
+                    entered.push((expr.syntax().kind(), depth));
+                    depth += 1;
+                }
+                WalkEvent::Leave(_) => depth -= 1,
+            }
+        }
+        assert_eq!(depth, 0);
+
+        use SyntaxKind::*;
+        // Identifiers in attrpaths are expressions too
+        assert_eq!(
+            entered,
+            [
+                (NODE_ROOT, 0),
+                (NODE_LET_IN, 1),
+                (NODE_IDENT, 2),
+                (NODE_LIST, 2),
+                (NODE_PAREN, 3),
+                (NODE_APPLY, 4),
+                (NODE_IDENT, 5),
+                (NODE_LITERAL, 5),
+                (NODE_ATTR_SET, 2),
+                (NODE_IDENT, 3),
+                (NODE_SELECT, 3),
+                (NODE_IDENT, 4),
+                (NODE_IDENT, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_trivia() {
+        let root = Root::parse("# set\n { a = 1; /* end */ } # done\n").syntax();
+        let first = first_token_skip_trivia(&root).unwrap();
+        assert_eq!(
+            (first.kind(), first.text_range().start()),
+            (SyntaxKind::TOKEN_L_BRACE, 7.into())
+        );
+        let last = last_token_skip_trivia(&root).unwrap();
+        assert_eq!(
+            (last.kind(), last.text_range().start()),
+            (SyntaxKind::TOKEN_R_BRACE, 26.into())
+        );
+
+        let set = typed_descendants::<ast::AttrSet>(&root).next().unwrap();
+        let value = set.attrpath_values().next().unwrap().value().unwrap();
+        assert_eq!(first_token_skip_trivia(value.syntax()).unwrap().text(), "1");
+        assert_eq!(last_token_skip_trivia(value.syntax()).unwrap().text(), "1");
+
+        let root = Root::parse(" # only a comment\n").syntax();
+        assert_eq!(first_token_skip_trivia(&root), None);
+        assert_eq!(last_token_skip_trivia(&root), None);
+    }
+
+    #[test]
+    fn at_offset() {
+        let root = Root::parse("f (a.bc)").syntax();
+        match token_at_offset(&root, 6.into()) {
+            TokenAtOffset::Single(token) => assert_eq!(token.text(), "bc"),
+            _ => panic!("expected a single token"),
+        }
+        assert_eq!(node_at_offset(&root, 6.into()).unwrap().kind(), SyntaxKind::NODE_IDENT);
+
+        match token_at_offset(&root, 4.into()) {
+            TokenAtOffset::Between(left, right) => {
+                assert_eq!((left.text(), right.text()), ("a", "."))
+            }
+            _ => panic!("expected two tokens"),
+        }
+        assert_eq!(node_at_offset(&root, 4.into()).unwrap().kind(), SyntaxKind::NODE_SELECT);
+        // Whitespace is skipped on either side
+        assert_eq!(node_at_offset(&root, 1.into()).unwrap().text(), "f");
+        assert_eq!(node_at_offset(&root, 2.into()).unwrap().kind(), SyntaxKind::NODE_PAREN);
+
+        assert!(matches!(token_at_offset(&root, 9.into()), TokenAtOffset::None));
+        assert_eq!(node_at_offset(&root, 9.into()), None);
+        assert_eq!(node_at_offset(&Root::parse("").syntax(), 0.into()), None);
+    }
+
+    #[test]
+    fn histogram() {
+        use SyntaxKind::*;
+
+        let root = Root::parse(r#"f "a${b}" (g c)"#).syntax();
+        let histogram = kind_histogram(&root);
+        let expected = [
+            (NODE_ROOT, 1),
+            (NODE_APPLY, 3),
+            (NODE_IDENT, 4),
+            (NODE_STRING, 1),
+            (NODE_INTERPOL, 1),
+            (NODE_PAREN, 1),
+            (TOKEN_IDENT, 4),
+            (TOKEN_STRING_START, 1),
+            (TOKEN_STRING_CONTENT, 1),
+            (TOKEN_INTERPOL_START, 1),
+            (TOKEN_INTERPOL_END, 1),
+            (TOKEN_STRING_END, 1),
+            (TOKEN_L_PAREN, 1),
+            (TOKEN_R_PAREN, 1),
+            (TOKEN_WHITESPACE, 3),
+        ];
+        assert_eq!(histogram, expected.into_iter().collect());
+    }
+}
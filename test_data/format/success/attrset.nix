@@ -0,0 +1,7 @@
+rec { a=1; b = { c = 2;   d = { }; };
+    "e f".g = rec {};
+
+
+  inherit   (x) y  z ;
+  inherit h;
+        ${i} = j ; }
@@ -0,0 +1,13 @@
+# Top level comment
+
+{ # after brace
+  a = 1; # trailing
+  /* block */ b = 2;
+
+  # Before c
+  c = # inside
+    3;
+  d = [ 1 # one
+    2 ];
+  e = f /* inline */ g;
+}
@@ -0,0 +1,4 @@
+let a = 1; b = let c = 2; in c;
+    f = x: y:
+      x + y;
+in f a b
@@ -0,0 +1,11 @@
+{
+  short = [1 2   (f x) "three"];
+  empty = [   ];
+  long = [ a
+    b c ];
+  nested = [ [ 1 2 ] { x = 1; } ];
+  strings = [ ''
+    indented ${ x }
+      string
+  '' "a  b" ./foo/${ bar } ];
+}
@@ -0,0 +1,11 @@
+{
+  a = 1+2*3;
+  b = x// y  //z;
+  c = !x||y&&z;
+  d = -1 - -2;
+  e = x.y or z;
+  f = x ? y;
+  g = [1]++[2];
+  h = if x==y then a else
+    b;
+}
@@ -0,0 +1,2 @@
+{ x = "oops
+ y = 1; }
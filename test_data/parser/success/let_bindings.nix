@@ -0,0 +1,9 @@
+let
+  a = 1;
+  b.c = 2;
+  "d" = 3;
+  ${e} = 4;
+  inherit f g;
+  inherit (set) h "i";
+in
+a
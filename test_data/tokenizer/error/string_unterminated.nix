@@ -0,0 +1,2 @@
+"oops
+ y
\ No newline at end of file
@@ -0,0 +1 @@
+[ 1.5 a.5 x.0 "a".1 (a).1 a .5 1. 1e3 ]
\ No newline at end of file
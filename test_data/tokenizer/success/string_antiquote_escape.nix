@@ -0,0 +1 @@
+"\${foo}"
\ No newline at end of file